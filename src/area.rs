@@ -119,6 +119,23 @@ where
     }
 }
 
+/// Make string of heart point id like `3💕`
+///
+/// Lower 4 bits of `id` is the heart type, and the rest is the hangul count.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::area;
+///
+/// assert_eq!("3💕", area::point_id_string((3 << 4) + 4));
+/// assert_eq!("0♡", area::point_id_string(13));
+/// ```
+pub fn point_id_string(id: u128) -> String {
+    let hearts = "?!♥❤💕💖💗💘💙💚💛💜💝♡".chars().collect::<Vec<char>>();
+    format!("{}{}", id >> 4, hearts[(id & 15) as usize])
+}
+
 /// `Area` to string in debug mode
 /// it builds the string as it iterates post-order
 pub fn area_to_string_debug(s: &mut String, area: &Area) {
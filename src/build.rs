@@ -1,6 +1,9 @@
 use crate::area::Area;
 use crate::code::Code;
+use crate::jump;
+use crate::jump::{Jump, JumpTable};
 use crate::number::Num;
+use crate::optimize;
 use crate::state::State;
 
 /// Makes indent with 4 spaces
@@ -28,7 +31,7 @@ fn vec_to_str(v: &Vec<Num>) -> String {
 }
 
 /// Makes the code from command.
-fn command(indent: usize, c: &impl Code) -> String {
+fn command(indent: usize, c: &impl Code, table: &JumpTable) -> String {
     String::from(format!(
         "{}{}",
         match c.get_type() {
@@ -113,7 +116,7 @@ fn command(indent: usize, c: &impl Code) -> String {
                 )
             }
         },
-        area(indent, c.get_area(), c.get_area_count())
+        area(indent, c.get_area(), c.get_area_count(), table)
     ))
 }
 
@@ -121,7 +124,8 @@ fn command(indent: usize, c: &impl Code) -> String {
 /// Since area consist of binary tree,
 /// we used match and recursively put content inside.
 /// (Didn't used recursive function)
-fn area(mut indent: usize, a: &Area, cnt: usize) -> String {
+/// Heart with static jump target goes directly to the target without `point`.
+fn area(mut indent: usize, a: &Area, cnt: usize, table: &JumpTable) -> String {
     let mut st = vec![(a, &Area::Nil, false)];
     let mut res = String::new();
     loop {
@@ -139,7 +143,20 @@ fn area(mut indent: usize, a: &Area, cnt: usize) -> String {
                     indent += 2;
                     continue;
                 } else {
+                    let id = ((cnt as u128) << 4) + *type_ as u128;
                     if *type_ < 13 {
+                        if let Some(Jump::Static(v)) = table.get(id) {
+                            res.push_str(&*format!(
+                                "\n{0}if state != {1} {{\
+                                 \n{0}    last = Option::Some(state);\
+                                 \n{0}    state = {1};\
+                                 \n{0}    continue;\
+                                 \n{0}}}",
+                                make_indent(indent),
+                                v
+                            ));
+                            break;
+                        }
                         res.push_str(&*format!(
                             "\n{0}let v = *point.entry({1}u128).or_insert(state);\
                              \n{0}if v != state {{\
@@ -148,7 +165,7 @@ fn area(mut indent: usize, a: &Area, cnt: usize) -> String {
                              \n{0}    continue;\
                              \n{0}}}",
                             make_indent(indent),
-                            ((cnt as u128) << 4) + *type_ as u128
+                            id
                         ));
                     } else {
                         res.push_str(&*format!(
@@ -198,6 +215,8 @@ fn area(mut indent: usize, a: &Area, cnt: usize) -> String {
 /// Since match is comparing linearly by each value,
 /// It makes binary if-else statement to minimize the comparision.
 /// So, in each movement, it would take `O(log S)`.
+/// Heart jumps that [jump::analyze](../jump/fn.analyze.html) can resolve
+/// go directly to the state without looking up `point`.
 pub fn build_source<T>(mut state: T, code: &Vec<T::CodeType>, level: usize) -> String
 where
     T: State,
@@ -341,7 +360,7 @@ fn main() {
             state
                 .get_stack(1)
                 .iter()
-                .map(|num| num.floor().to_int() as u8 as char)
+                .map(optimize::output_char)
                 .collect(),
         ));
         state.get_stack(1).clear();
@@ -353,7 +372,7 @@ fn main() {
             state
                 .get_stack(2)
                 .iter()
-                .map(|num| num.floor().to_int() as u8 as char)
+                .map(optimize::output_char)
                 .collect(),
         ));
         state.get_stack(2).clear();
@@ -363,6 +382,9 @@ fn main() {
         let mut codes: Vec<Vec<T::CodeType>> = Vec::new();
         codes.push(Vec::new());
 
+        let mut all_code = Vec::new();
+        let mut start = 0;
+
         if level >= 2 {
             for i in state.get_all_stack_index() {
                 if state.get_stack(i).is_empty() {
@@ -391,55 +413,18 @@ fn main() {
                 }
             ));
 
-            let mut point = state.get_all_point();
-            point.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-            let mut idx = 0;
-
-            for (i, c) in state.get_all_code().iter().enumerate() {
-                match c.get_area() {
-                    Area::Val {
-                        type_: _,
-                        left: _,
-                        right: _,
-                    } => {
-                        if !codes.last().unwrap().is_empty() {
-                            codes.push(vec![c.clone()]);
-                        } else {
-                            codes.last_mut().unwrap().push(c.clone());
-                        }
-                        while idx < point.len() && point[idx].1 == i {
-                            point[idx].1 = codes.len() - 1;
-                            idx += 1;
-                        }
-                        codes.push(Vec::new());
-                    }
-                    Area::Nil => {
-                        codes.last_mut().unwrap().push(c.clone());
-                    }
-                }
-            }
+            all_code = state.get_all_code();
+            start = all_code.len();
+        }
+        all_code.extend(code.iter().cloned());
 
-            if opt {
-                for (a, b) in point {
-                    res.push_str(&*format!(
-                        "
-    point.insert({}u128, {});",
-                        a, b
-                    ));
-                }
+        // block index of each command
+        let mut block = Vec::with_capacity(all_code.len());
 
-                res.push_str(&*format!(
-                    "
-    state = {};",
-                    codes.len(),
-                ));
-            }
-            if !codes.last().unwrap().is_empty() {
+        for (i, c) in all_code.iter().enumerate() {
+            if i == start && !codes.last().unwrap().is_empty() {
                 codes.push(Vec::new());
             }
-        }
-
-        for c in code {
             match c.get_area() {
                 Area::Val {
                     type_: _,
@@ -451,14 +436,37 @@ fn main() {
                     } else {
                         codes.last_mut().unwrap().push(c.clone());
                     }
+                    block.push(codes.len() - 1);
                     codes.push(Vec::new());
                 }
                 Area::Nil => {
                     codes.last_mut().unwrap().push(c.clone());
+                    block.push(codes.len() - 1);
                 }
             }
         }
 
+        let mut point = state.get_all_point();
+        point.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        if level >= 2 && opt {
+            for (a, b) in &point {
+                res.push_str(&*format!(
+                    "
+    point.insert({}u128, {});",
+                    a, block[*b]
+                ));
+            }
+
+            res.push_str(&*format!(
+                "
+    state = {};",
+                block[start],
+            ));
+        }
+
+        let table = jump::analyze(&all_code, start, &point).map_location(|x| block[x]);
+
         if codes.last().unwrap().is_empty() {
             codes.pop().unwrap();
         }
@@ -484,7 +492,7 @@ fn main() {
             }
 
             for item in &codes[i] {
-                res.push_str(&*command(indent, item));
+                res.push_str(&*command(indent, item, &table));
             }
 
             while stack.len() > 1 && stack.last().unwrap().1 {
@@ -51,6 +51,8 @@ pub struct OptCode {
     dot_count: usize,
     area_count: usize,
     area: Area,
+    // index of `UnOptCode` that this code is made from
+    source: Option<usize>,
 }
 
 impl OptCode {
@@ -88,8 +90,32 @@ impl OptCode {
             dot_count,
             area_count,
             area,
+            source: None,
         }
     }
+
+    /// Set index of source `UnOptCode`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::code::OptCode;
+    /// use hyeong::area::Area;
+    ///
+    /// let mut a = OptCode::new(0, 1, 1, 0, Area::Nil);
+    /// assert_eq!(None, a.get_source());
+    ///
+    /// a.set_source(3);
+    /// assert_eq!(Some(3), a.get_source());
+    /// ```
+    pub fn set_source(&mut self, loc: usize) {
+        self.source = Option::Some(loc);
+    }
+
+    /// Return index of source `UnOptCode`
+    pub fn get_source(&self) -> Option<usize> {
+        self.source
+    }
 }
 
 impl Code for OptCode {
@@ -0,0 +1,519 @@
+use crate::code::UnOptCode;
+use crate::debug::{History, Program};
+use crate::io::{CustomWriter, QueueReader};
+use crate::number::Num;
+use crate::parse;
+use crate::state::{State, UnOptState};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{stdin, stdout, BufRead, BufReader, Write};
+use std::process;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+/// Debug adapter structure
+///
+/// It keeps the history like [debug::run](../debug/fn.run.html)
+/// and talks with editor by [Debug Adapter Protocol](https://microsoft.github.io/debug-adapter-protocol/).
+/// Requests are received from `requests`, so they can be read while the program runs.
+struct Adapter<W>
+where
+    W: Write,
+{
+    output: W,
+    requests: Receiver<Value>,
+    seq: u64,
+    path: String,
+    code: Vec<UnOptCode>,
+    program: Program,
+    ipt: QueueReader,
+    history: History<UnOptState>,
+    break_points: HashSet<usize>,
+    stop_on_entry: bool,
+    terminated: bool,
+}
+
+/// Read one message from `input`
+/// Returns `None` when input is closed.
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return Option::None;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(v) = line.strip_prefix("Content-Length:") {
+            length = v.trim().parse::<usize>().ok()?;
+        }
+    }
+
+    let mut buf = vec![0u8; length];
+    input.read_exact(&mut buf).ok()?;
+    serde_json::from_slice(&buf).ok()
+}
+
+/// Make variable object of DAP
+fn variable(name: String, value: String, reference: usize) -> Value {
+    json!({
+        "name": name,
+        "value": value,
+        "variablesReference": reference,
+    })
+}
+
+/// Make string of stack
+fn stack_to_string(v: &[Num]) -> String {
+    format!(
+        "[{}]",
+        v.iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+impl<W> Adapter<W>
+where
+    W: Write,
+{
+    fn new(output: W, requests: Receiver<Value>) -> Adapter<W> {
+        Adapter {
+            output,
+            requests,
+            seq: 0,
+            path: String::new(),
+            code: vec![],
+            program: Program::new("", &[]),
+            ipt: QueueReader::new(),
+            history: History::new(UnOptState::new(), 0),
+            break_points: HashSet::new(),
+            stop_on_entry: false,
+            terminated: false,
+        }
+    }
+
+    /// Send message with `Content-Length` header
+    fn send(&mut self, mut msg: Value) {
+        self.seq += 1;
+        msg["seq"] = json!(self.seq);
+        let body = msg.to_string();
+        write!(
+            self.output,
+            "Content-Length: {}\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        self.output.flush().unwrap();
+    }
+
+    fn respond(&mut self, req: &Value, body: Value) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": req["seq"],
+            "success": true,
+            "command": req["command"],
+            "body": body,
+        }));
+    }
+
+    fn respond_error(&mut self, req: &Value, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": req["seq"],
+            "success": false,
+            "command": req["command"],
+            "message": message,
+        }));
+    }
+
+    fn event(&mut self, event: &str, body: Value) {
+        self.send(json!({
+            "type": "event",
+            "event": event,
+            "body": body,
+        }));
+    }
+
+    fn stopped(&mut self, reason: &str) {
+        self.event(
+            "stopped",
+            json!({
+                "reason": reason,
+                "threadId": 1,
+                "allThreadsStopped": true,
+            }),
+        );
+    }
+
+    fn exited(&mut self, code: i32) {
+        self.terminated = true;
+        self.event("exited", json!({ "exitCode": code }));
+        self.event("terminated", json!({}));
+    }
+
+    fn cur_loc(&self) -> usize {
+        self.history.loc
+    }
+
+    /// Execute one command and send the output
+    /// Returns false if the program is finished.
+    fn step(&mut self) -> bool {
+        if self.terminated {
+            return false;
+        }
+        if self.cur_loc() >= self.code.len() {
+            self.exited(0);
+            return false;
+        }
+
+        let mut out = CustomWriter::new(|_| Result::Ok(()));
+        let mut err = CustomWriter::new(|_| Result::Ok(()));
+        let res = self.history.execute(&mut self.ipt, &mut out, &mut err);
+
+        for (category, s) in &[("stdout", out.to_string()), ("stderr", err.to_string())] {
+            if !s.is_empty() {
+                self.event("output", json!({ "category": category, "output": s }));
+            }
+        }
+
+        if let Some(code) = res {
+            self.exited(code);
+            return false;
+        }
+
+        if self.cur_loc() >= self.code.len() {
+            self.exited(0);
+            return false;
+        }
+        true
+    }
+
+    /// Run until breakpoint, checking requests between commands
+    /// `pause` stops the program, and requests that move the program get error response.
+    /// Returns false if the session is over.
+    fn run(&mut self) -> bool {
+        loop {
+            match self.requests.try_recv() {
+                Ok(req) => match req["command"].as_str().unwrap_or("") {
+                    "pause" => {
+                        self.respond(&req, json!({}));
+                        self.stopped("pause");
+                        return true;
+                    }
+                    "launch" | "configurationDone" | "next" | "stepIn" | "stepOut" | "continue"
+                    | "stepBack" | "reverseContinue" => {
+                        self.respond_error(&req, "program is running");
+                    }
+                    _ => {
+                        if !self.handle(req) {
+                            return false;
+                        }
+                    }
+                },
+                Err(TryRecvError::Empty) => (),
+                Err(TryRecvError::Disconnected) => return false,
+            }
+
+            if !self.step() {
+                return true;
+            }
+            if self.break_points.contains(&self.cur_loc()) {
+                self.stopped("breakpoint");
+                return true;
+            }
+        }
+    }
+
+    /// Run backward until breakpoint
+    fn reverse_run(&mut self) {
+        let break_points = &self.break_points;
+        let found = self
+            .history
+            .find_back(&self.program, &mut self.ipt, |_, loc, _| {
+                break_points.contains(&loc)
+            });
+        self.history
+            .goto(&self.program, found.unwrap_or(0), &mut self.ipt)
+            .unwrap();
+        if found.is_some() {
+            self.stopped("breakpoint");
+        } else {
+            self.stopped("entry");
+        }
+    }
+
+    /// Set breakpoints of given lines
+    /// Breakpoint is on the first command of the line (and column).
+    fn set_break_points(&mut self, req: &Value) -> Value {
+        self.break_points.clear();
+        let mut res = Vec::new();
+
+        if let Some(v) = req["arguments"]["breakpoints"].as_array() {
+            for b in v {
+                let line = b["line"].as_u64().unwrap_or(0) as usize;
+                let col = b["column"].as_u64().map(|x| (x as usize).saturating_sub(1));
+                let found = self.code.iter().position(|c| {
+                    let (l, c) = c.get_location();
+                    l == line && col.unwrap_or(0) <= c
+                });
+
+                match found {
+                    Some(i) => {
+                        self.break_points.insert(i);
+                        res.push(json!({
+                            "verified": true,
+                            "line": line,
+                            "column": self.code[i].get_location().1 + 1,
+                        }));
+                    }
+                    None => res.push(json!({ "verified": false, "line": line })),
+                }
+            }
+        }
+
+        json!({ "breakpoints": res })
+    }
+
+    fn stack_trace(&self) -> Value {
+        if self.terminated || self.cur_loc() >= self.code.len() {
+            return json!({ "stackFrames": [], "totalFrames": 0 });
+        }
+        let c = &self.code[self.cur_loc()];
+        json!({
+            "stackFrames": [{
+                "id": 0,
+                "name": format!("{}: {}", self.cur_loc(), c.get_raw()),
+                "line": c.get_location().0,
+                "column": c.get_location().1 + 1,
+                "source": { "path": self.path },
+            }],
+            "totalFrames": 1,
+        })
+    }
+
+    /// Variables of reference
+    /// - `1`: current stack and all stacks
+    /// - `i + 2`: values of stack `i`
+    fn variables(&mut self, reference: usize) -> Value {
+        let state = &mut self.history.state;
+        let mut res = Vec::new();
+
+        if reference == 1 {
+            res.push(variable(
+                "current stack".to_string(),
+                state.current_stack().to_string(),
+                0,
+            ));
+            let mut v = state.get_all_stack_index();
+            v.sort();
+            for i in v {
+                res.push(variable(
+                    format!("stack {}", i),
+                    stack_to_string(state.get_stack(i)),
+                    if state.get_stack(i).is_empty() {
+                        0
+                    } else {
+                        i + 2
+                    },
+                ));
+            }
+        } else if reference >= 2 {
+            for (i, n) in state.get_stack(reference - 2).iter().enumerate() {
+                res.push(variable(i.to_string(), n.to_string(), 0));
+            }
+        }
+
+        json!({ "variables": res })
+    }
+
+    fn launch(&mut self, req: &Value) -> Result<(), String> {
+        let args = &req["arguments"];
+        self.path = args["program"]
+            .as_str()
+            .ok_or_else(|| "program is not given".to_string())?
+            .to_string();
+        self.code = parse::parse(fs::read_to_string(&self.path).map_err(|e| e.to_string())?);
+        self.ipt = QueueReader::new();
+        self.ipt.push(args["input"].as_str().unwrap_or(""));
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+        let mut state = UnOptState::new();
+        for c in &self.code {
+            state.push_code(c.clone());
+        }
+        self.program = Program::new(&self.path, &self.code);
+        self.history = History::new(state, 0);
+        self.terminated = false;
+        Result::Ok(())
+    }
+
+    /// Handle request
+    /// Returns false if the session is over.
+    fn handle(&mut self, req: Value) -> bool {
+        match req["command"].as_str().unwrap_or("") {
+            "initialize" => {
+                self.respond(
+                    &req,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsStepBack": true,
+                    }),
+                );
+            }
+
+            "launch" => match self.launch(&req) {
+                Ok(_) => {
+                    self.respond(&req, json!({}));
+                    self.event("initialized", json!({}));
+                }
+                Err(e) => self.respond_error(&req, &e),
+            },
+
+            "setBreakpoints" => {
+                let body = self.set_break_points(&req);
+                self.respond(&req, body);
+            }
+
+            "configurationDone" => {
+                self.respond(&req, json!({}));
+                if self.stop_on_entry {
+                    self.stopped("entry");
+                } else if self.break_points.contains(&self.cur_loc()) {
+                    self.stopped("breakpoint");
+                } else {
+                    return self.run();
+                }
+            }
+
+            "threads" => {
+                self.respond(&req, json!({ "threads": [{ "id": 1, "name": "main" }] }));
+            }
+
+            "stackTrace" => {
+                let body = self.stack_trace();
+                self.respond(&req, body);
+            }
+
+            "scopes" => {
+                self.respond(
+                    &req,
+                    json!({
+                        "scopes": [{
+                            "name": "Stacks",
+                            "variablesReference": 1,
+                            "expensive": false,
+                        }]
+                    }),
+                );
+            }
+
+            "variables" => {
+                let reference = req["arguments"]["variablesReference"].as_u64().unwrap_or(0);
+                let body = self.variables(reference as usize);
+                self.respond(&req, body);
+            }
+
+            "next" | "stepIn" | "stepOut" => {
+                self.respond(&req, json!({}));
+                if self.step() {
+                    self.stopped("step");
+                }
+            }
+
+            "continue" => {
+                self.respond(&req, json!({ "allThreadsContinued": true }));
+                return self.run();
+            }
+
+            "stepBack" => {
+                self.respond(&req, json!({}));
+                // exit by popping stack 1, 2 is also a recorded step
+                if self.history.step > 0 {
+                    let step = self.history.step - 1;
+                    self.history
+                        .goto(&self.program, step, &mut self.ipt)
+                        .unwrap();
+                }
+                self.terminated = false;
+                self.stopped("step");
+            }
+
+            "reverseContinue" => {
+                self.respond(&req, json!({}));
+                self.reverse_run();
+                self.terminated = false;
+            }
+
+            // program is already stopped
+            "pause" => {
+                self.respond(&req, json!({}));
+            }
+
+            "disconnect" | "terminate" => {
+                self.respond(&req, json!({}));
+                return false;
+            }
+
+            t => {
+                self.respond_error(&req, &*format!("command \"{}\" not supported", t));
+            }
+        }
+        true
+    }
+}
+
+/// Serves Debug Adapter Protocol from `input` to `output`
+///
+/// Supported requests are `initialize`, `launch`, `setBreakpoints`, `configurationDone`,
+/// `threads`, `stackTrace`, `scopes`, `variables`, `next`, `stepIn`, `stepOut`,
+/// `continue`, `stepBack`, `reverseContinue`, `pause`, `disconnect` and `terminate`.
+/// Requests are read on another thread, so running program can be paused or disconnected.
+///
+/// `launch` gets `program` for the path of code,
+/// `input` for the input of program and `stopOnEntry`.
+/// `input` is read line by line like stdin.
+/// Output of program is sent by `output` event.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::dap;
+/// use std::io::Cursor;
+///
+/// let req = r#"{"seq":1,"type":"request","command":"threads"}"#;
+/// let input = format!("Content-Length: {}\r\n\r\n{}", req.len(), req);
+/// let mut output = Vec::new();
+///
+/// dap::serve(Cursor::new(input), &mut output);
+/// assert!(String::from_utf8(output).unwrap().contains("\"name\":\"main\""));
+/// ```
+pub fn serve(mut input: impl BufRead + Send + 'static, output: impl Write) {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        while let Some(req) = read_message(&mut input) {
+            if tx.send(req).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut adapter = Adapter::new(output, rx);
+    while let Ok(req) = adapter.requests.recv() {
+        if !adapter.handle(req) {
+            break;
+        }
+    }
+}
+
+/// Runs Debug Adapter Protocol server on stdin and stdout
+#[cfg_attr(tarpaulin, skip)]
+pub fn run() -> ! {
+    serve(BufReader::new(stdin()), stdout());
+    process::exit(0);
+}
@@ -1,54 +1,1319 @@
-use crate::code::UnOptCode;
-use crate::state::{State, UnOptState};
-use crate::{execute, io};
+use crate::area::{point_id_string, Area};
+use crate::code::{Code, OptCode, UnOptCode};
+use crate::io::ReadLine;
+use crate::number::Num;
+use crate::state::{OptState, State, UnOptState};
+use crate::{execute, io, optimize};
 use colored::Colorize;
-use std::collections::HashSet;
-use std::io::{stdin, stdout, Write};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{stdin, stdout, IsTerminal, Read, Write};
 use std::process;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+/// Find command index from location string
+///
+/// Location can be one of below.
+/// Line and column are same as printed in `hyeong check`.
+/// 1. `LINE`: first command on `LINE` or after
+/// 2. `LINE:COL`: first command on `LINE:COL` or after
+/// 3. `FILE:LINE`, `FILE:LINE:COL`: same as above but checks the file name
+/// 4. `#NUM`: `NUM`th command
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{debug, parse};
+///
+/// let code = parse::parse("형. 형..\n\n흑... 항.".to_string());
+///
+/// assert_eq!(Ok(2), debug::find_location(&code, "a.hyeong", "2"));
+/// assert_eq!(Ok(1), debug::find_location(&code, "a.hyeong", "1:1"));
+/// assert_eq!(Ok(3), debug::find_location(&code, "a.hyeong", "a.hyeong:3:4"));
+/// assert_eq!(Ok(1), debug::find_location(&code, "a.hyeong", "#1"));
+/// assert!(debug::find_location(&code, "a.hyeong", "4").is_err());
+/// assert!(debug::find_location(&code, "a.hyeong", "b.hyeong:1").is_err());
+/// ```
+pub fn find_location(code: &[UnOptCode], file: &str, loc: &str) -> Result<usize, String> {
+    let loc = loc.trim();
+    let parse_num = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| format!("invalid location \"{}\"", loc))
+    };
+
+    if let Some(num) = loc.strip_prefix('#') {
+        let num = parse_num(num)?;
+        return if num < code.len() {
+            Ok(num)
+        } else {
+            Err("number exceeds the range".to_string())
+        };
+    }
+
+    let v = loc.rsplitn(3, ':').collect::<Vec<_>>();
+    let (f, line, col) = match v.len() {
+        1 => (None, parse_num(v[0])?, 0),
+        2 => match v[1].parse::<usize>() {
+            Ok(line) => (None, line, parse_num(v[0])?),
+            Err(_) => (Some(v[1].to_string()), parse_num(v[0])?, 0),
+        },
+        _ => match v[1].parse::<usize>() {
+            Ok(line) => (Some(v[2].to_string()), line, parse_num(v[0])?),
+            Err(_) => (Some(format!("{}:{}", v[2], v[1])), parse_num(v[0])?, 0),
+        },
+    };
+
+    if let Some(f) = f {
+        if !file.ends_with(&*f) {
+            return Err(format!("file \"{}\" is not being debugged", f));
+        }
+    }
+
+    match code.iter().position(|c| c.get_location() >= (line, col)) {
+        Some(i) => Ok(i),
+        None => Err(format!("no command on {}:{} or after", line, col)),
+    }
+}
+
+/// Stack numbers of optimized code and dot counts of source code
+/// It is identity if code is not optimized.
+#[derive(Default)]
+struct StackMap {
+    // stack count of `OptState`
+    size: Option<usize>,
+    // stack index to dot counts
+    names: HashMap<usize, Vec<usize>>,
+    // dot count to stack index
+    ids: HashMap<usize, usize>,
+}
+
+impl StackMap {
+    fn new(source: &[UnOptCode], code: &[OptCode], size: usize) -> StackMap {
+        let mut ids = HashMap::new();
+        for c in code {
+            if let Some(t) = c.get_source() {
+                let dot = source[t].get_dot_count();
+                if c.get_type() != 0 && dot > 3 && c.get_dot_count() < size {
+                    ids.insert(dot, c.get_dot_count());
+                }
+            }
+        }
+        let mut names: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (dot, id) in &ids {
+            names.entry(*id).or_default().push(*dot);
+        }
+        for v in names.values_mut() {
+            v.sort_unstable();
+        }
+
+        StackMap {
+            size: Some(size),
+            names,
+            ids,
+        }
+    }
+
+    /// Stack index of dot count
+    fn id(&self, dot: usize) -> Result<usize, String> {
+        match self.size {
+            Some(_) if dot > 3 => self
+                .ids
+                .get(&dot)
+                .copied()
+                .ok_or_else(|| format!("stack {} is removed by optimization", dot)),
+            _ => Ok(dot),
+        }
+    }
+
+    /// First dot count of stack index
+    fn dot(&self, id: usize) -> usize {
+        self.names.get(&id).map_or(id, |x| x[0])
+    }
+
+    /// Dot counts of stack index, joined with `,`
+    fn name(&self, id: usize) -> String {
+        match self.names.get(&id) {
+            Some(v) => v
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => id.to_string(),
+        }
+    }
+}
+
+/// Code being debugged and its source code
+pub(crate) struct Program {
+    file: String,
+    source: Vec<UnOptCode>,
+    // index of source code of each command
+    map: Vec<usize>,
+    stacks: StackMap,
+}
+
+impl Program {
+    /// Program of unoptimized `code` in `file`
+    pub(crate) fn new(file: &str, code: &[UnOptCode]) -> Program {
+        Program {
+            file: file.to_string(),
+            source: code.to_vec(),
+            map: (0..code.len()).collect(),
+            stacks: StackMap::default(),
+        }
+    }
+
+    /// String of command location: `LINE:COL|NUM`
+    fn location_string(&self, idx: usize) -> String {
+        let (line, col) = self.source[self.map[idx]].get_location();
+        format!("{}:{}|{}", line, col, idx)
+    }
+
+    /// Raw source code of command
+    fn raw(&self, idx: usize) -> String {
+        self.source[self.map[idx]].get_raw()
+    }
+
+    /// Find command index from location string
+    /// `#NUM` is the index of command, and others are mapped from source code.
+    fn find_location(&self, loc: &str) -> Result<usize, String> {
+        if let Some(num) = loc.trim().strip_prefix('#') {
+            return match num.parse::<usize>() {
+                Ok(t) if t < self.map.len() => Ok(t),
+                Ok(_) => Err("number exceeds the range".to_string()),
+                Err(_) => Err(format!("invalid location \"{}\"", loc)),
+            };
+        }
+        let i = find_location(&self.source, &self.file, loc)?;
+        self.map
+            .iter()
+            .position(|&x| x >= i)
+            .ok_or_else(|| format!("no command on {} or after", loc))
+    }
+}
+
+/// String of state with stack names
+fn state_string<T: State>(state: &mut T, stacks: &StackMap) -> String {
+    let mut s = format!("current stack: {}\n", stacks.name(state.current_stack()));
+    let mut v = state.get_all_stack_index();
+    v.sort_by_key(|&x| stacks.dot(x));
+    for i in v {
+        s.push_str(&*format!(
+            "stack {}: {:?}\n",
+            stacks.name(i),
+            state.get_stack(i)
+        ));
+    }
+    s
+}
+
+/// Parse number written like `-3/4`
+fn parse_number(s: &str) -> Result<Num, String> {
+    if !s.is_empty()
+        && s.trim_start_matches('-')
+            .split('/')
+            .all(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit()))
+    {
+        Ok(Num::from_string(s.to_string()))
+    } else {
+        Err(format!("invalid number \"{}\"", s))
+    }
+}
+
+/// Parse heart point id written like `3💕` (area count and heart)
+fn parse_point_id(s: &str) -> Result<u128, String> {
+    let hearts = "?!♥❤💕💖💗💘💙💚💛💜💝♡".chars().collect::<Vec<char>>();
+    let err = || format!("invalid point \"{}\", e.g. 3💕", s);
+
+    let c = s.chars().last().ok_or_else(err)?;
+    let type_ = hearts[2..13].iter().position(|&x| x == c).ok_or_else(err)? as u128 + 2;
+    let count = s[..s.len() - c.len_utf8()]
+        .parse::<u128>()
+        .map_err(|_| err())?;
+    Ok((count << 4) + type_)
+}
+
+/// Information of points or jumps for `info` command
+fn info<T: State>(
+    history: &History<T>,
+    program: &Program,
+    args: &[&str],
+) -> Result<String, String> {
+    let command = |x: usize| format!("{} {}", program.location_string(x), program.raw(x));
+    let mut res = String::new();
+    match args.get(1) {
+        Some(&"points") if args.len() == 2 => {
+            let mut v = history.state.get_all_point();
+            v.sort();
+            for (id, loc) in v {
+                res.push_str(&*format!("{} {}\n", point_id_string(id), command(loc)));
+            }
+            res.push_str(&*format!(
+                "latest: {}\n",
+                history
+                    .state
+                    .get_latest_loc()
+                    .map(command)
+                    .unwrap_or_else(|| "none".to_string())
+            ));
+        }
+        Some(&"jumps") if args.len() <= 3 => {
+            let n = match args.get(2).map(|x| x.parse::<usize>()) {
+                Some(Ok(t)) => t,
+                Some(Err(_)) => return Err(format!("usage: {}", usage("info"))),
+                None => 10,
+            };
+            let skip = history.jumps.len().saturating_sub(n);
+            for (step, from, to) in history.jumps.iter().skip(skip) {
+                res.push_str(&*format!(
+                    "step {}: {} -> {}\n",
+                    step,
+                    command(*from),
+                    command(*to)
+                ));
+            }
+        }
+        _ => return Err(format!("usage: {}", usage("info"))),
+    }
+    Ok(res)
+}
+
+/// Edit `state` by debugger command and return log message
+/// Commands do not read or write program input and output.
+/// Stack numbers are dot counts of source code.
+fn edit<T: State>(state: &mut T, program: &Program, args: &[&str]) -> Result<String, String> {
+    let arg = |i: usize| {
+        args.get(i)
+            .ok_or_else(|| format!("usage: {}", usage(args[0])))
+    };
+    let int = |i: usize| arg(i)?.parse::<usize>().map_err(|e| e.to_string());
+    let stack = |i: usize| int(i).and_then(|x| program.stacks.id(x));
+    let loc = |i: usize| program.find_location(arg(i)?);
+    let num = |i: usize| arg(i).and_then(|x| parse_number(x));
+
+    match args[0] {
+        "push" => {
+            let (idx, n) = (stack(1)?, num(2)?);
+            let res = format!("pushed {} to stack {}", n, arg(1)?);
+            state.push_stack(idx, n);
+            Ok(res)
+        }
+        "pop" => {
+            let idx = stack(1)?;
+            if !state.get_all_stack_index().contains(&idx) || state.get_stack(idx).is_empty() {
+                return Err(format!("stack {} is empty", arg(1)?));
+            }
+            Ok(format!(
+                "popped {} from stack {}",
+                state.pop_stack(idx),
+                arg(1)?
+            ))
+        }
+        "put" => {
+            let (idx, i, n) = (stack(1)?, int(2)?, num(3)?);
+            if !state.get_all_stack_index().contains(&idx) {
+                return Err(format!("stack {} is empty", arg(1)?));
+            }
+            let st = state.get_stack(idx);
+            if i >= st.len() {
+                return Err(format!("stack {} has only {} values", arg(1)?, st.len()));
+            }
+            let res = format!("overwrote {} with {} on stack {}", st[i], n, arg(1)?);
+            st[i] = n;
+            Ok(res)
+        }
+        "cur" => {
+            let idx = stack(1)?;
+            state.set_current_stack(idx);
+            Ok(format!("current stack is {}", arg(1)?))
+        }
+        "point" => {
+            let id = parse_point_id(arg(1)?)?;
+            if args.len() < 3 {
+                state.remove_point(id);
+                Ok(format!("cleared point {}", point_id_string(id)))
+            } else {
+                let l = loc(2)?;
+                state.set_point(id, l);
+                Ok(format!(
+                    "set point {} on {}",
+                    point_id_string(id),
+                    program.location_string(l)
+                ))
+            }
+        }
+        // latest
+        _ => {
+            let l = loc(1)?;
+            state.set_latest_loc(l);
+            Ok(format!(
+                "set latest location {}",
+                program.location_string(l)
+            ))
+        }
+    }
+}
+
+/// Usage of state editing commands
+fn usage(cmd: &str) -> &'static str {
+    match cmd {
+        "push" => "push STACK VALUE",
+        "pop" => "pop STACK",
+        "put" => "put STACK IDX VALUE",
+        "cur" => "cur STACK",
+        "point" => "point HEART [LOC]",
+        "info" => "info points | info jumps [N]",
+        _ => "latest LOC",
+    }
+}
+
+/// Maximum number of checkpoints kept in the history
+const MAX_CHECKPOINT: usize = 256;
+
+/// Maximum number of jumps kept in the history
+const MAX_JUMP: usize = 1024;
+
+/// Event recorded in the history
+enum Event {
+    /// Executed `n` commands
+    Step(usize),
+    /// Edited state with arguments of the command
+    Edit(Vec<String>),
+}
+
+/// State without code at some step
+struct Checkpoint<T: State> {
+    step: usize,
+    state: T,
+    loc: usize,
+    input: usize,
+}
+
+/// Execution history of debugger
+///
+/// Instead of keeping every state, it keeps events and checkpoints.
+/// Earlier steps are restored from the nearest checkpoint and executed again.
+/// Checkpoints are thinned out so that there are at most `MAX_CHECKPOINT` of them.
+/// Program input is replayed from `io::QueueReader` that keeps every line.
+/// It is also used by [dap](../dap/index.html) to step back.
+pub(crate) struct History<T: State> {
+    pub(crate) state: T,
+    pub(crate) loc: usize,
+    pub(crate) step: usize,
+    // recorded steps, it is bigger than `step` after going back
+    last: usize,
+    // events with its first step
+    events: Vec<(usize, Event)>,
+    checkpoints: Vec<Checkpoint<T>>,
+    interval: usize,
+    // recent jumps as step, location before and after
+    jumps: VecDeque<(usize, usize, usize)>,
+    // state to swap with while executing
+    spare: Option<T>,
+}
+
+impl<T> History<T>
+where
+    T: State + Clone,
+{
+    pub(crate) fn new(state: T, loc: usize) -> History<T> {
+        let mut res = History {
+            state,
+            loc,
+            step: 0,
+            last: 0,
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+            interval: 1024,
+            jumps: VecDeque::new(),
+            spare: None,
+        };
+        res.checkpoint(0);
+        res.spare = Some(res.checkpoints[0].state.clone());
+        res
+    }
+
+    /// Save checkpoint of current state
+    fn checkpoint(&mut self, input: usize) {
+        let mut code = Vec::new();
+        self.state.swap_code(&mut code);
+        self.checkpoints.push(Checkpoint {
+            step: self.step,
+            state: self.state.clone(),
+            loc: self.loc,
+            input,
+        });
+        self.state.swap_code(&mut code);
+
+        if self.checkpoints.len() > MAX_CHECKPOINT {
+            self.interval *= 2;
+            let interval = self.interval;
+            self.checkpoints.retain(|x| x.step % interval == 0);
+        }
+    }
+
+    /// Record event after current step, forgetting steps after it
+    fn record(&mut self, event: Event, input: usize) {
+        if self.step < self.last {
+            let step = self.step;
+            let i = self.events.partition_point(|x| x.0 < step);
+            self.events.truncate(i);
+            if let Some((start, Event::Step(n))) = self.events.last_mut() {
+                *n = (*n).min(step - *start);
+            }
+            self.checkpoints.retain(|x| x.step <= step);
+        }
+
+        match (self.events.last_mut(), event) {
+            (Some((_, Event::Step(n))), Event::Step(m)) => *n += m,
+            (_, event) => self.events.push((self.step, event)),
+        }
+        self.step += 1;
+        self.last = self.step;
+
+        if self.step % self.interval == 0 {
+            self.checkpoint(input);
+        }
+    }
+
+    /// Execute one command and record it
+    /// Returns exit code if the command pops stack 1 or 2.
+    /// The step is recorded with the state at that pop, and location is not moved.
+    pub(crate) fn execute(
+        &mut self,
+        ipt: &mut io::QueueReader,
+        out: &mut impl Write,
+        err: &mut impl Write,
+    ) -> Option<i32> {
+        let res = self.execute_one(ipt, out, err);
+        self.record(Event::Step(1), ipt.position());
+        res
+    }
+
+    /// Execute one command without recording
+    fn execute_one(
+        &mut self,
+        ipt: &mut io::QueueReader,
+        out: &mut impl Write,
+        err: &mut impl Write,
+    ) -> Option<i32> {
+        let state = std::mem::replace(&mut self.state, self.spare.take().unwrap());
+        let (state, res) = execute::execute_one_no_exit(ipt, out, err, state, self.loc);
+        self.spare = Some(std::mem::replace(&mut self.state, state));
+        let loc = match res {
+            Ok(t) => t,
+            Err(code) => return Some(code),
+        };
+
+        // jumps are already kept when replaying
+        if loc != self.loc + 1 && self.jumps.back().map_or(true, |x| x.0 < self.step) {
+            self.jumps.push_back((self.step, self.loc, loc));
+            if self.jumps.len() > MAX_JUMP {
+                self.jumps.pop_front();
+            }
+        }
+        self.loc = loc;
+        None
+    }
+
+    /// Edit state and record it
+    fn edit(&mut self, program: &Program, args: &[&str], input: usize) -> Result<String, String> {
+        let res = edit(&mut self.state, program, args)?;
+        self.record(
+            Event::Edit(args.iter().map(|x| x.to_string()).collect()),
+            input,
+        );
+        Ok(res)
+    }
+
+    /// Restore nearest checkpoint on or before `step`
+    fn restore(&mut self, step: usize, ipt: &mut io::QueueReader) {
+        let i = self.checkpoints.partition_point(|x| x.step <= step) - 1;
+        let mut code = Vec::new();
+        self.state.swap_code(&mut code);
+        self.state = self.checkpoints[i].state.clone();
+        self.state.swap_code(&mut code);
+        self.step = self.checkpoints[i].step;
+        self.loc = self.checkpoints[i].loc;
+        ipt.set_position(self.checkpoints[i].input);
+        while self.jumps.back().is_some_and(|x| x.0 >= self.step) {
+            self.jumps.pop_back();
+        }
+    }
+
+    /// Execute recorded event of current step again without output
+    fn replay_one(&mut self, program: &Program, ipt: &mut io::QueueReader) {
+        let step = self.step;
+        let i = self.events.partition_point(|x| x.0 <= step) - 1;
+        match &self.events[i].1 {
+            Event::Step(_) => {
+                let mut out = io::CustomWriter::new(|_| Result::Ok(()));
+                let mut err = io::CustomWriter::new(|_| Result::Ok(()));
+                self.execute_one(ipt, &mut out, &mut err);
+            }
+            Event::Edit(args) => {
+                let args = args.clone();
+                let args = args.iter().map(|x| x.as_str()).collect::<Vec<_>>();
+                let _ = edit(&mut self.state, program, &args);
+            }
+        }
+        self.step += 1;
+    }
+
+    /// Move to recorded `step`
+    pub(crate) fn goto(
+        &mut self,
+        program: &Program,
+        step: usize,
+        ipt: &mut io::QueueReader,
+    ) -> Result<(), String> {
+        if step > self.last {
+            return Err(format!(
+                "step {} is not executed yet, last step is {}",
+                step, self.last
+            ));
+        }
+
+        let i = self.checkpoints.partition_point(|x| x.step <= step) - 1;
+        if step < self.step || self.checkpoints[i].step > self.step {
+            self.restore(step, ipt);
+        }
+        while self.step < step {
+            self.replay_one(program, ipt);
+        }
+        Ok(())
+    }
+
+    /// Find the latest step before current step that `hit` returns true
+    /// `hit` gets state, location and step.
+    /// State is not restored, so call `goto` after this.
+    pub(crate) fn find_back<F>(
+        &mut self,
+        program: &Program,
+        ipt: &mut io::QueueReader,
+        mut hit: F,
+    ) -> Option<usize>
+    where
+        F: FnMut(&mut T, usize, usize) -> bool,
+    {
+        let mut end = self.step;
+
+        for i in (0..self.checkpoints.len()).rev() {
+            let start = self.checkpoints[i].step;
+            if start >= end {
+                continue;
+            }
+
+            self.restore(start, ipt);
+            let mut found = None;
+            loop {
+                if hit(&mut self.state, self.loc, self.step) {
+                    found = Some(self.step);
+                }
+                if self.step + 1 >= end {
+                    break;
+                }
+                self.replay_one(program, ipt);
+            }
+
+            if found.is_some() {
+                return found;
+            }
+            end = start;
+        }
+
+        None
+    }
+}
+
+/// Value in the condition
+#[derive(Clone)]
+enum Value {
+    Top(usize),
+    Len(usize),
+    Cur,
+    Step,
+    Const(Num),
+}
+
+impl Value {
+    fn parse(s: &str) -> Result<Value, String> {
+        let stack_of = |t: &str| {
+            t.trim()
+                .strip_suffix(')')
+                .and_then(|x| x.trim().parse::<usize>().ok())
+                .ok_or_else(|| format!("invalid value \"{}\"", s))
+        };
+
+        let s = s.trim();
+        if let Some(t) = s.strip_prefix("top(") {
+            Ok(Value::Top(stack_of(t)?))
+        } else if let Some(t) = s.strip_prefix("len(") {
+            Ok(Value::Len(stack_of(t)?))
+        } else if s == "cur" {
+            Ok(Value::Cur)
+        } else if s == "step" {
+            Ok(Value::Step)
+        } else {
+            parse_number(s)
+                .map(Value::Const)
+                .map_err(|_| format!("invalid value \"{}\"", s))
+        }
+    }
+
+    fn eval<T: State>(&self, state: &mut T, step: usize, stacks: &StackMap) -> Num {
+        let id = |state: &T, dot: &usize| {
+            stacks
+                .id(*dot)
+                .ok()
+                .filter(|x| state.get_all_stack_index().contains(x))
+        };
+        match self {
+            Value::Top(i) => match id(state, i).and_then(|x| state.get_stack(x).last().cloned()) {
+                Some(t) => t,
+                None => Num::nan(),
+            },
+            Value::Len(i) => match id(state, i) {
+                Some(x) => Num::from_num(state.get_stack(x).len() as isize),
+                None => Num::zero(),
+            },
+            Value::Cur => Num::from_num(stacks.dot(state.current_stack()) as isize),
+            Value::Step => Num::from_num(step as isize),
+            Value::Const(n) => n.clone(),
+        }
+    }
+}
+
+/// Condition of breakpoint
+///
+/// It is comparisons of values joined with `&&` and `||`. (`&&` first)
+///
+/// - values: `top(N)`, `len(N)`, `cur`, `step` or number (`3`, `-1`, `1/2`)
+/// - comparisons: `==`, `!=`, `<`, `<=`, `>`, `>=`
+///
+/// `top(N)` of empty stack is NaN and only `!=` is true with NaN.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::debug::Condition;
+/// use hyeong::number::Num;
+/// use hyeong::state::{State, UnOptState};
+///
+/// let mut s = UnOptState::new();
+/// s.push_stack(5, Num::zero());
+///
+/// let c = Condition::parse("top(5) == 0 && len(3) < 100").unwrap();
+/// assert_eq!(true, c.eval(&mut s, 0));
+///
+/// let c = Condition::parse("cur == 7 || step >= 10").unwrap();
+/// assert_eq!(false, c.eval(&mut s, 3));
+/// assert_eq!(true, c.eval(&mut s, 10));
+///
+/// assert!(Condition::parse("top(5) = 0").is_err());
+/// ```
+#[derive(Clone)]
+pub struct Condition {
+    raw: String,
+    or: Vec<Vec<(Value, String, Value)>>,
+}
+
+impl Condition {
+    /// Parse the condition
+    pub fn parse(s: &str) -> Result<Condition, String> {
+        let mut or = Vec::new();
+        for a in s.split("||") {
+            let mut and = Vec::new();
+            for c in a.split("&&") {
+                let op = ["==", "!=", "<=", ">=", "<", ">"]
+                    .iter()
+                    .find(|x| c.contains(*x))
+                    .ok_or_else(|| format!("no comparison in \"{}\"", c.trim()))?;
+                let v = c.splitn(2, op).collect::<Vec<_>>();
+                and.push((Value::parse(v[0])?, op.to_string(), Value::parse(v[1])?));
+            }
+            or.push(and);
+        }
+        Ok(Condition {
+            raw: s.split_whitespace().collect::<Vec<_>>().join(" "),
+            or,
+        })
+    }
+
+    /// Evaluate the condition
+    /// `step` is the count of executed commands.
+    pub fn eval<T: State>(&self, state: &mut T, step: usize) -> bool {
+        self.eval_with(state, step, &StackMap::default())
+    }
+
+    /// Evaluate the condition with stack numbers of source code
+    fn eval_with<T: State>(&self, state: &mut T, step: usize, stacks: &StackMap) -> bool {
+        self.or.iter().any(|and| {
+            and.iter().all(|(lhs, op, rhs)| {
+                let ord = lhs
+                    .eval(state, step, stacks)
+                    .partial_cmp(&rhs.eval(state, step, stacks));
+                match &**op {
+                    "==" => ord == Some(std::cmp::Ordering::Equal),
+                    "!=" => ord != Some(std::cmp::Ordering::Equal),
+                    "<" => ord == Some(std::cmp::Ordering::Less),
+                    ">" => ord == Some(std::cmp::Ordering::Greater),
+                    "<=" => matches!(ord, Some(x) if x != std::cmp::Ordering::Greater),
+                    _ => matches!(ord, Some(x) if x != std::cmp::Ordering::Less),
+                }
+            })
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+/// Where running stops other than breakpoints, watchpoints and conditions
+#[derive(Clone, Copy)]
+enum Target {
+    /// `run`: never stops
+    End,
+    /// `over`: stops on the command
+    Loc(usize),
+    /// `finish`: stops when ♡ returns to the latest location or its heart loop exits
+    Latest(usize),
+}
+
+impl Target {
+    /// Return true if running from `prev` to `loc` reaches the target
+    fn reached(self, prev: usize, loc: usize) -> bool {
+        match self {
+            Target::End => false,
+            Target::Loc(x) => loc == x,
+            // loop jumping back from `x` exits by passing it, and ♡ jumps to `x` itself
+            Target::Latest(x) => loc > x || (loc == x && prev + 1 != x),
+        }
+    }
+}
+
+/// Stacks that are pushed or popped when running command of `loc`
+fn touched_stacks<T: State>(state: &T, loc: usize) -> Vec<usize> {
+    let code = state.get_code(loc);
+    let cur = state.current_stack();
+    let mut res = vec![cur];
+
+    if code.get_type() != 0 {
+        res.push(code.get_dot_count());
+    }
+    if let Area::Val { type_, .. } = code.get_area() {
+        if *type_ <= 1 {
+            res.push(if code.get_type() == 5 {
+                code.get_dot_count()
+            } else {
+                cur
+            });
+        }
+    }
+    res
+}
+
+/// Return true if running command of `loc` reads a line from program input
+fn needs_input<T: State>(state: &mut T, loc: usize) -> bool {
+    let mut len = if state.get_all_stack_index().contains(&0) {
+        state.get_stack(0).len()
+    } else {
+        0
+    };
+    let code = state.get_code(loc);
+    let cur = state.current_stack();
+
+    if code.get_type() == 0 {
+        if cur == 0 {
+            len += 1;
+        }
+    } else {
+        let pop = if code.get_type() == 5 {
+            1
+        } else {
+            code.get_hangul_count()
+        };
+        if cur == 0 {
+            if len < pop {
+                return true;
+            }
+            len -= pop;
+            len += match code.get_type() {
+                3 | 4 => pop,
+                5 => 1,
+                _ => 0,
+            };
+        }
+        if code.get_dot_count() == 0 {
+            len += if code.get_type() == 5 {
+                code.get_hangul_count()
+            } else {
+                1
+            };
+        }
+    }
+
+    let cur = if code.get_type() == 5 {
+        code.get_dot_count()
+    } else {
+        cur
+    };
+    matches!(code.get_area(), Area::Val { type_, .. } if *type_ <= 1) && cur == 0 && len == 0
+}
+
+/// Way of reading debugger commands
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Read commands line by line with prompts
+    Line,
+    /// Read commands line by line without prompts, for scripts
+    Batch,
+    /// Full-screen view with commands bound to keys
+    /// Falls back to `Line` when not on a terminal.
+    Screen,
+}
+
+/// Keys of full-screen view and the commands bound to them
+const KEYS: [(char, &str, &str); 10] = [
+    ('n', "next", "next"),
+    ('o', "over", "over"),
+    ('f', "finish", "finish"),
+    ('p', "previous", "prev"),
+    ('r', "run", "run"),
+    ('v', "reverse", "reverse"),
+    ('b', "break", "break"),
+    ('s', "state", "state"),
+    ('h', "help", "help"),
+    ('q', "exit", "quit"),
+];
+
+/// Number of lines of program output shown in full-screen view
+const OUTPUT_LINES: usize = 4;
+
+/// Cut string to fit in `width` of terminal
+fn fit(s: &str, width: usize) -> String {
+    let mut w = 0;
+    s.chars()
+        .take_while(|&c| {
+            w += io::char_width(c);
+            w <= width
+        })
+        .collect()
+}
+
+/// Full-screen view drawn with ANSI escape codes
+///
+/// The top of the terminal shows source code, stacks, heart points and program output,
+/// and the bottom is scrolled for logs and commands typed after `:`.
+struct Screen {
+    lines: Vec<String>,
+    // terminal settings to restore
+    saved: String,
+    size: Cell<(usize, usize)>,
+    // program output shown in the view
+    output: Rc<RefCell<Vec<String>>>,
+}
+
+impl Screen {
+    /// Make new screen, `None` if stdin or stdout is not a terminal
+    fn new(file: &str, output: Rc<RefCell<Vec<String>>>) -> Option<Screen> {
+        if !stdin().is_terminal() || !stdout().is_terminal() {
+            return None;
+        }
+        let saved = io::stty(&["-g"])?;
+        io::stty(&["-icanon", "-echo", "min", "1"])?;
+        let screen = Screen {
+            lines: io::read_text(file)
+                .lines()
+                .map(|x| x.replace('\t', " "))
+                .collect(),
+            saved,
+            size: Cell::new((0, 0)),
+            output,
+        };
+        print!("\x1b[2J");
+        screen.resize();
+        Some(screen)
+    }
+
+    /// Height of the view on top
+    fn height(&self) -> usize {
+        let rows = self.size.get().0;
+        rows - (rows / 4).max(5)
+    }
+
+    /// Read terminal size and set scroll region below the view
+    fn resize(&self) {
+        let size = io::stty(&["size"])
+            .and_then(|s| {
+                let mut it = s.split_whitespace().map(|x| x.parse::<usize>().ok());
+                Some((it.next()??, it.next()??))
+            })
+            .unwrap_or((24, 80));
+        let size = (size.0.max(12), size.1.max(20));
+        if size != self.size.get() {
+            self.size.set(size);
+            print!("\x1b[{};{}r\x1b[{};1H", self.height() + 1, size.0, size.0);
+        }
+    }
+
+    /// Source line `line` with command on `hl` highlighted
+    fn source_line(&self, line: usize, width: usize, hl: Option<(usize, usize)>) -> String {
+        let chars = self
+            .lines
+            .get(line - 1)
+            .map(|x| x.chars().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let (a, b) = hl.unwrap_or((0, 0));
+        let (a, b) = (a.min(chars.len()), b.min(chars.len()));
+
+        // scroll horizontally to show the command with a few characters after it
+        let mut skip = 0;
+        while skip < a
+            && chars[skip..b]
+                .iter()
+                .map(|&c| io::char_width(c))
+                .sum::<usize>()
+                + 8
+                > width
+        {
+            skip += 1;
+        }
+
+        let line = fit(&chars[skip..].iter().collect::<String>(), width);
+        let len = line.chars().count();
+        let (a, b) = (a - skip, (b - skip).min(len));
+        if hl.is_none() || a >= len {
+            return line;
+        }
+        let part = |x: usize, y: usize| line.chars().skip(x).take(y - x).collect::<String>();
+        format!(
+            "{}{}{}",
+            part(0, a),
+            part(a, b).black().on_bright_yellow(),
+            part(b, len)
+        )
+    }
+
+    /// Draw the view
+    fn draw<T: State>(
+        &self,
+        state: &mut T,
+        loc: usize,
+        step: usize,
+        program: &Program,
+        break_points: &HashMap<usize, Option<Condition>>,
+    ) {
+        self.resize();
+        let (height, width) = (self.height(), self.size.get().1);
+        let rule = |s: &str| {
+            let s = format!("── {} ", s);
+            let w = s.chars().map(io::char_width).sum::<usize>();
+            format!("{}{}", s, "─".repeat(width.saturating_sub(w))).bright_black()
+        };
+        let keys = KEYS
+            .iter()
+            .map(|(k, _, name)| format!("{}:{}", k, name))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut view = vec![fit(
+            &*format!(" {} | step {} | {} :command", program.file, step, keys),
+            width,
+        )
+        .reversed()
+        .to_string()];
+
+        // stacks and points first to know the space left for source
+        let mut panels = Vec::new();
+        let mut v = state.get_all_stack_index();
+        v.sort_by_key(|&x| program.stacks.dot(x));
+        panels.push(rule("stacks").to_string());
+        let cur = state.current_stack();
+        for i in v {
+            let s = state.get_stack(i);
+            if s.is_empty() && i != cur {
+                continue;
+            }
+            let values = s.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            let head = format!(
+                "{} {:>5}: ",
+                if i == cur { ">" } else { " " },
+                program.stacks.name(i)
+            );
+            // show top of stack when it is too long
+            let mut body = values.join(" ");
+            let room = width.saturating_sub(head.chars().count());
+            let mut n = 0;
+            while body.chars().map(io::char_width).sum::<usize>() > room {
+                n += 1;
+                body = format!("... {}", values[n..].join(" "));
+            }
+            let line = format!("{}{}", head, body);
+            panels.push(if i == cur {
+                line.bold().to_string()
+            } else {
+                line
+            });
+        }
+
+        let latest = state
+            .get_latest_loc()
+            .map(|x| program.location_string(x))
+            .unwrap_or_else(|| "-".to_string());
+        panels.push(rule(&*format!("points, latest: {}", latest)).to_string());
+        let mut points = state.get_all_point();
+        points.sort();
+        panels.push(fit(
+            &points
+                .iter()
+                .map(|(id, x)| format!("{} {}", point_id_string(*id), program.location_string(*x)))
+                .collect::<Vec<_>>()
+                .join("  "),
+            width,
+        ));
+
+        panels.push(rule("output").to_string());
+        let output = self.output.borrow();
+        for i in 0..OUTPUT_LINES {
+            let idx = (output.len() + i).checked_sub(OUTPUT_LINES);
+            panels.push(fit(idx.map(|x| output[x].as_str()).unwrap_or(""), width));
+        }
+        drop(output);
+
+        // source around current command
+        let rows = height.saturating_sub(1 + panels.len()).max(1);
+        let (line, col) = if loc < program.map.len() {
+            program.source[program.map[loc]].get_location()
+        } else {
+            (self.lines.len().max(1), 0)
+        };
+        let breaks = break_points
+            .keys()
+            .map(|&x| program.source[program.map[x]].get_location().0)
+            .collect::<HashSet<_>>();
+        let first = line.saturating_sub(rows / 2).max(1);
+        let digits = (first + rows).to_string().len();
+        for i in first..first + rows {
+            if i > self.lines.len() {
+                view.push(String::new());
+                continue;
+            }
+            let head = format!(
+                "{}{}{:>w$} ",
+                if breaks.contains(&i) { "*" } else { " " },
+                if i == line { ">" } else { " " },
+                i,
+                w = digits
+            );
+            let hl = if i == line && loc < program.map.len() {
+                Some((col, col + program.raw(loc).chars().count()))
+            } else {
+                None
+            };
+            let body = self.source_line(i, width.saturating_sub(head.len()), hl);
+            view.push(format!("{}{}", head.bright_black(), body));
+        }
+        view.append(&mut panels);
+
+        print!("\x1b7");
+        for (i, s) in view.iter().take(height).enumerate() {
+            print!("\x1b[{};1H{}\x1b[0m\x1b[K", i + 1, s);
+        }
+        print!("\x1b8");
+        io::handle_error(stdout().flush());
+    }
+
+    /// Read a key and return the command bound to it
+    /// `:` reads a whole command line from `cmd`.
+    /// `b` toggles breakpoint on `loc`.
+    fn read_key(&self, loc: usize, cmd: &mut impl ReadLine) -> String {
+        let mut buf = [0u8; 1];
+        loop {
+            if stdin().lock().read(&mut buf).unwrap_or(0) == 0 {
+                return String::new();
+            }
+            match buf[0] {
+                // Ctrl-D
+                4 => return String::new(),
+                b':' => {
+                    io::stty(&[&*self.saved]);
+                    print!("{} ", ":".bright_red());
+                    io::handle_error(stdout().flush());
+                    let res = io::read_line_from(cmd);
+                    io::stty(&["-icanon", "-echo", "min", "1"]);
+                    return res;
+                }
+                b'b' => return format!("break #{}\n", loc),
+                c => {
+                    if let Some((_, cmd, _)) = KEYS.iter().find(|x| x.0 as u8 == c) {
+                        return format!("{}\n", cmd);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Restore the terminal
+    fn close(&self) {
+        print!("\x1b[r\x1b[{};1H", self.size.get().0);
+        io::handle_error(stdout().flush());
+        io::stty(&[&*self.saved]);
+    }
+}
+
 /// Debug function
 ///
 /// It works like interpreter but accepting commands below.
-/// 1. break(b)       show breakpoints");
-/// 2. break(b) NUM   set/unset breakpoint on NUM");
-/// 3. exit           Exit debugger");
-/// 4. help(h)        Print this");
-/// 5. next(n)        goto next command");
-/// 6. state(s)       print state status");
-/// 7. previous(p)    move to previous state");
-/// 8. run(r)         run until breakpoint");
+/// 1. back N               move back N steps");
+/// 2. break(b)             show breakpoints");
+/// 3. break(b) LOC         set/unset breakpoint on LOC");
+/// 4. break(b) LOC if EXPR set breakpoint on LOC with condition");
+/// 5. break(b) if EXPR     set/unset breakpoint when EXPR becomes true");
+/// 6. cur STACK            set current stack");
+/// 7. exit                 Exit debugger");
+/// 8. finish(f)            run until ♡ returns to latest location or its loop exits");
+/// 9. goto STEP            move to STEP");
+/// 10. help(h)             Print this");
+/// 11. info jumps [N]      show last N jumps (10 by default)");
+/// 12. info points         show heart points and latest location");
+/// 13. input(i)            show queued program input");
+/// 14. input(i) TEXT       queue TEXT as a line of program input");
+/// 15. latest LOC          set latest location used by ♡");
+/// 16. next(n)             goto next command");
+/// 17. over(o)             run until next command of current one");
+/// 18. point HEART         clear point of HEART");
+/// 19. point HEART LOC     set point of HEART to LOC");
+/// 20. pop STACK           pop value from STACK");
+/// 21. push STACK VALUE    push VALUE to STACK");
+/// 22. put STACK IDX VALUE overwrite IDXth value from bottom of STACK");
+/// 23. state(s)            print state status");
+/// 24. previous(p)         move to previous state");
+/// 25. reverse(rr)         run backward until breakpoint");
+/// 26. run(r)              run until breakpoint");
+/// 27. watch(w)            show watchpoints");
+/// 28. watch(w) NUM        set/unset watchpoint on stack NUM");
+///
+/// `LOC` is `LINE`, `LINE:COL`, `FILE:LINE` or `#NUM`.
+/// See [find_location](fn.find_location.html).
+/// `EXPR` is a [Condition](struct.Condition.html).
+/// `HEART` is area count followed by heart, like `3💕`.
+/// Editing commands change the state directly and can be undone by `previous`.
+/// Every command and edit is one step, and `goto` can move to any step executed before.
+/// Commands are read from `cmd` as `mode`, see [Mode](enum.Mode.html).
+/// In full-screen view, keys `n`, `o`, `f`, `p`, `r`, `v`, `b`, `s`, `h`, `q` run next, over,
+/// finish, previous, run, reverse, break on current command, state, help and exit,
+/// and `:` reads any command above.
+/// Watchpoint stops after the command that pushes to or pops from the stack.
+/// `over` and `finish` also stop on breakpoints, watchpoints and conditions like `run`.
+/// Program reads input only from `ipt`, never from debugger commands.
+/// It stops before the command that needs more input than queued.
+#[cfg_attr(tarpaulin, skip)]
+pub fn run(
+    file: &str,
+    code: Vec<UnOptCode>,
+    from: usize,
+    ipt: io::QueueReader,
+    cmd: impl ReadLine,
+    mode: Mode,
+) -> ! {
+    let program = Program::new(file, &code);
+    run_state(program, UnOptState::new(), code, from, ipt, cmd, mode)
+}
+
+/// Debug function for optimized code
+///
+/// `opt` is the result of `optimize::optimize` of `source`.
+/// It works same as [run](fn.run.html) except stack numbers.
+/// Stacks are shown and given as dot counts of `source`,
+/// and stacks that are never the current stack are shown together like `6,7`.
+/// `from` is the index of `source`.
+#[cfg_attr(tarpaulin, skip)]
+pub fn run_opt(
+    file: &str,
+    source: Vec<UnOptCode>,
+    opt: (OptState, Vec<OptCode>),
+    from: usize,
+    ipt: io::QueueReader,
+    cmd: impl ReadLine,
+    mode: Mode,
+) -> ! {
+    let (state, code) = opt;
+    let all_code = state
+        .get_all_code()
+        .into_iter()
+        .chain(code.iter().cloned())
+        .collect::<Vec<_>>();
+    let program = Program {
+        file: file.to_string(),
+        stacks: StackMap::new(&source, &all_code, state.stack_size()),
+        source,
+        map: all_code
+            .iter()
+            .map(|x| x.get_source().unwrap_or(0))
+            .collect(),
+    };
+    run_state(program, state, code, from, ipt, cmd, mode)
+}
+
+/// Debug function for any state
 #[cfg_attr(tarpaulin, skip)]
-pub fn run(code: Vec<UnOptCode>, from: usize) -> ! {
+fn run_state<T>(
+    program: Program,
+    mut state: T,
+    code: Vec<T::CodeType>,
+    from: usize,
+    mut ipt: io::QueueReader,
+    mut cmd: impl ReadLine,
+    mode: Mode,
+) -> !
+where
+    T: State + Clone,
+{
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
-    let mut state = UnOptState::new();
 
-    ctrlc::set_handler(move || {
-        if r.load(Ordering::SeqCst) {
-            r.store(false, Ordering::SeqCst);
-            print!("\ntype \"exit\" to exit\n");
-            print!("{} ", ">".bright_red());
-            io::handle_error(stdout().flush());
-            r.store(true, Ordering::SeqCst);
-        }
-    })
-    .expect("Error setting Ctrl-C handler");
+    if mode != Mode::Batch {
+        ctrlc::set_handler(move || {
+            if r.load(Ordering::SeqCst) {
+                r.store(false, Ordering::SeqCst);
+                print!("\ntype \"exit\" to exit\n");
+                print!("{} ", ">".bright_red());
+                io::handle_error(stdout().flush());
+                r.store(true, Ordering::SeqCst);
+            }
+        })
+        .expect("Error setting Ctrl-C handler");
+    }
 
     io::print_log("running in debug mode");
 
-    for c in &code {
-        state.push_code(c.clone());
-    }
-
     let mut is_running = false;
-    let mut break_points = HashSet::new();
-    break_points.insert(from);
+    let mut skip_break = false;
+    let mut target = Target::End;
+    // breakpoint location with its condition
+    let mut break_points: HashMap<usize, Option<Condition>> = HashMap::new();
+    // conditions that stop when it becomes true with its last value
+    let mut conditions: Vec<(Condition, bool)> = Vec::new();
+    let mut watch_points = HashSet::new();
+
+    // program output shown in full-screen view
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let push_output = |x: &str, name: &str| {
+        for s in x.lines() {
+            output.borrow_mut().push(format!("[{}] {}", name, s));
+        }
+    };
 
     let mut out = io::CustomWriter::new(|x| {
         if !x.is_empty() {
             println!("[{}] {}", "stdout".bold(), x);
+            push_output(&x, "stdout");
         }
 
         Result::Ok(())
@@ -57,37 +1322,166 @@ pub fn run(code: Vec<UnOptCode>, from: usize) -> ! {
     let mut err = io::CustomWriter::new(|x| {
         if !x.is_empty() {
             println!("[{}] {}", "stderr".bold().bright_red(), x);
+            push_output(&x, "stderr");
         }
 
         Result::Ok(())
     });
 
-    let mut state_stack = vec![(state, 0)];
+    // level 2 optimization can leave output
+    for i in 1..=2 {
+        if state.get_all_stack_index().contains(&i) {
+            let s = state
+                .get_stack(i)
+                .drain(..)
+                .map(|x| optimize::output_char(&x))
+                .collect::<String>();
+            if i == 1 {
+                io::write(&mut out, &s);
+            } else {
+                io::write(&mut err, &s);
+            }
+        }
+    }
+    out.flush().unwrap();
+    err.flush().unwrap();
+
+    let start = state.get_all_code().len();
+    let len = start + code.len();
+    for c in code {
+        state.push_code(c);
+    }
+
+    // `from` is the index of source code
+    let from = (start..len)
+        .find(|&i| program.map[i] >= from)
+        .unwrap_or(start);
+    break_points.insert(from, None);
 
-    while state_stack.last().unwrap().1 < code.len() {
+    let mut history = History::new(state, start);
+
+    let screen = if mode == Mode::Screen {
+        let res = Screen::new(&program.file, output.clone());
+        if res.is_none() {
+            io::print_warn("not on a terminal, falling back to line mode");
+        }
+        res
+    } else {
+        None
+    };
+    // terminal should be restored before the program exits
+    let close = |state: &T, loc: usize| {
+        if let Some(s) = &screen {
+            if loc >= len || execute::exit_code(state, loc).is_some() {
+                s.close();
+            }
+        }
+    };
+
+    while history.loc < len {
         if is_running {
-            if break_points.contains(&state_stack.last().unwrap().1) {
+            let (state, loc) = (&mut history.state, &mut history.loc);
+            let hit = match break_points.get(loc) {
+                Some(Some(c)) => c.eval_with(state, history.step, &program.stacks),
+                Some(None) => true,
+                None => false,
+            };
+
+            if !skip_break && hit {
                 out.flush().unwrap();
                 err.flush().unwrap();
                 is_running = false;
-            } else {
-                state_stack.push(execute::execute_one(
-                    &mut stdin(),
-                    &mut out,
-                    &mut err,
-                    state_stack.last().unwrap().0.clone(),
-                    state_stack.last().unwrap().1,
+                io::print_log(&*format!(
+                    "stopped by breakpoint on {}",
+                    program.location_string(*loc)
+                ));
+                continue;
+            }
+            skip_break = false;
+
+            if ipt.is_empty() && needs_input(state, *loc) {
+                out.flush().unwrap();
+                err.flush().unwrap();
+                is_running = false;
+                io::print_log(&*format!(
+                    "waiting for input on {}, use \"input TEXT\"",
+                    program.location_string(*loc)
                 ));
+                continue;
+            }
+
+            let touched = touched_stacks(state, *loc);
+            let prev = *loc;
+            close(state, *loc);
+            if let Some(code) = history.execute(&mut ipt, &mut out, &mut err) {
+                out.flush().unwrap();
+                err.flush().unwrap();
+                process::exit(code);
+            }
+
+            let (state, loc) = (&mut history.state, &mut history.loc);
+            let mut reason = Vec::new();
+
+            for i in touched {
+                let name = format!("stack {}", program.stacks.name(i));
+                if watch_points.contains(&i) && !reason.contains(&name) {
+                    reason.push(name);
+                }
+            }
+            for (c, last) in conditions.iter_mut() {
+                let now = c.eval_with(state, history.step, &program.stacks);
+                if now && !*last {
+                    reason.push(format!("\"{}\"", c));
+                }
+                *last = now;
+            }
+
+            if !reason.is_empty() {
+                out.flush().unwrap();
+                err.flush().unwrap();
+                is_running = false;
+                if *loc < len {
+                    io::print_log(&*format!(
+                        "stopped by {} on {}",
+                        reason.join(", "),
+                        program.location_string(*loc)
+                    ));
+                }
+            } else if target.reached(prev, *loc) {
+                out.flush().unwrap();
+                err.flush().unwrap();
+                is_running = false;
+                if *loc < len {
+                    io::print_log(&*format!(
+                        "{} {}",
+                        if let Target::Loc(_) = target {
+                            "stepped over to"
+                        } else {
+                            "finished on"
+                        },
+                        program.location_string(*loc)
+                    ));
+                }
             }
         } else {
             loop {
-                print!("{} ", ">".bright_red());
-                io::handle_error(stdout().flush());
+                if mode != Mode::Batch && screen.is_none() {
+                    print!("{} ", ">".bright_red());
+                    io::handle_error(stdout().flush());
+                }
                 running.store(true, Ordering::SeqCst);
-                let input = io::read_line();
+                let input = match &screen {
+                    Some(s) => {
+                        let (state, loc) = (&mut history.state, history.loc);
+                        s.draw(state, loc, history.step, &program, &break_points);
+                        s.read_key(loc, &mut cmd)
+                    }
+                    None => io::read_line_from(&mut cmd),
+                };
                 running.store(false, Ordering::SeqCst);
 
-                if input == "" {
+                if input.is_empty() {
+                    close(&history.state, len);
                     process::exit(0);
                 }
 
@@ -95,62 +1489,237 @@ pub fn run(code: Vec<UnOptCode>, from: usize) -> ! {
 
                 match parsed[0] {
                     "next" | "n" => {
-                        let c = &code[state_stack.last().unwrap().1];
+                        if ipt.is_empty() && needs_input(&mut history.state, history.loc) {
+                            io::print_error_str_no_exit(
+                                "program is waiting for input, use \"input TEXT\"",
+                            );
+                            continue;
+                        }
 
                         println!(
-                            "{}:{}|{} {}",
-                            c.get_location().0,
-                            c.get_location().1,
-                            state_stack.last().unwrap().1,
-                            c.get_raw().bright_blue()
+                            "{} {}",
+                            program.location_string(history.loc),
+                            program.raw(history.loc).bright_blue()
                         );
 
-                        state_stack.push(execute::execute_one(
-                            &mut stdin(),
-                            &mut out,
-                            &mut err,
-                            state_stack.last().unwrap().0.clone(),
-                            state_stack.last().unwrap().1,
-                        ));
+                        close(&history.state, history.loc);
+                        let res = history.execute(&mut ipt, &mut out, &mut err);
 
                         out.flush().unwrap();
                         err.flush().unwrap();
+                        if let Some(code) = res {
+                            process::exit(code);
+                        }
 
                         break;
                     }
 
                     "previous" | "p" => {
-                        if state_stack.len() > 1 {
-                            state_stack.pop();
+                        if history.step > 0 {
+                            history.goto(&program, history.step - 1, &mut ipt).unwrap();
                             io::print_log("moved back");
                         } else {
                             io::print_error_str_no_exit("cannot go back");
                         }
                     }
 
-                    "run" | "r" => {
-                        state_stack.push(execute::execute_one(
-                            &mut stdin(),
-                            &mut out,
-                            &mut err,
-                            state_stack.last().unwrap().0.clone(),
-                            state_stack.last().unwrap().1,
+                    "back" | "goto" => {
+                        let num = match parsed.get(1).map(|x| x.parse::<usize>()) {
+                            Some(Ok(t)) => t,
+                            Some(Err(e)) => {
+                                io::print_error_no_exit(e);
+                                continue;
+                            }
+                            None if parsed[0] == "back" => 1,
+                            None => {
+                                io::print_error_str_no_exit("usage: goto STEP");
+                                continue;
+                            }
+                        };
+                        let step = if parsed[0] == "goto" {
+                            num
+                        } else if num <= history.step {
+                            history.step - num
+                        } else {
+                            io::print_error_str_no_exit("cannot go back before step 0");
+                            continue;
+                        };
+
+                        match history.goto(&program, step, &mut ipt) {
+                            Ok(()) => io::print_log(&*format!(
+                                "moved to step {} on {}",
+                                history.step,
+                                program.location_string(history.loc)
+                            )),
+                            Err(e) => io::print_error_str_no_exit(&e),
+                        }
+                    }
+
+                    "reverse" | "rr" => {
+                        if history.step == 0 {
+                            io::print_error_str_no_exit("cannot go back");
+                            continue;
+                        }
+                        let found = history.find_back(&program, &mut ipt, |state, loc, step| {
+                            match break_points.get(&loc) {
+                                Some(Some(c)) => c.eval_with(state, step, &program.stacks),
+                                Some(None) => true,
+                                None => false,
+                            }
+                        });
+                        history
+                            .goto(&program, found.unwrap_or(0), &mut ipt)
+                            .unwrap();
+                        io::print_log(&*format!(
+                            "{}moved to step {} on {}",
+                            if found.is_some() {
+                                ""
+                            } else {
+                                "no breakpoint before, "
+                            },
+                            history.step,
+                            program.location_string(history.loc)
                         ));
+                    }
+
+                    "run" | "r" | "over" | "o" | "finish" | "f" => {
+                        target = match parsed[0] {
+                            "over" | "o" => Target::Loc(history.loc + 1),
+                            "finish" | "f" => match history.state.get_latest_loc() {
+                                Some(t) => Target::Latest(t),
+                                None => {
+                                    io::print_error_str_no_exit("no latest location to finish");
+                                    continue;
+                                }
+                            },
+                            _ => Target::End,
+                        };
+                        for (c, last) in conditions.iter_mut() {
+                            *last = c.eval_with(&mut history.state, history.step, &program.stacks);
+                        }
 
                         is_running = true;
+                        skip_break = true;
                         break;
                     }
 
                     "state" | "s" => {
-                        print!("{:?}", state_stack.last().unwrap().0);
+                        println!("step: {}", history.step);
+                        print!("{}", state_string(&mut history.state, &program.stacks));
                     }
 
                     "break" | "b" => {
                         if parsed.len() < 2 {
                             let mut v = break_points.iter().collect::<Vec<_>>();
-                            v.sort();
+                            v.sort_by_key(|x| x.0);
+                            for (i, c) in v {
+                                print!(
+                                    "{} {}",
+                                    program.location_string(*i),
+                                    program.raw(*i).bright_blue()
+                                );
+                                match c {
+                                    Some(c) => println!(" if {}", c),
+                                    None => println!(),
+                                }
+                            }
+                            for (c, _) in &conditions {
+                                println!("if {}", c);
+                            }
+                            continue;
+                        }
+
+                        if parsed[1] == "if" {
+                            let c = match Condition::parse(&parsed[2..].join(" ")) {
+                                Ok(t) => t,
+                                Err(e) => {
+                                    io::print_error_str_no_exit(&e);
+                                    continue;
+                                }
+                            };
+                            match conditions.iter().position(|x| x.0.raw == c.raw) {
+                                Some(i) => {
+                                    conditions.remove(i);
+                                    io::print_log(&*format!("unset breakpoint if {}", c));
+                                }
+                                None => {
+                                    io::print_log(&*format!("set breakpoint if {}", c));
+                                    conditions.push((c, false));
+                                }
+                            }
+                            continue;
+                        }
+
+                        let num = match program.find_location(parsed[1]) {
+                            Ok(t) => t,
+                            Err(e) => {
+                                io::print_error_str_no_exit(&e);
+                                continue;
+                            }
+                        };
+
+                        if parsed.len() > 2 {
+                            if parsed[2] != "if" {
+                                io::print_error_str_no_exit("expected \"if\" after location");
+                                continue;
+                            }
+                            match Condition::parse(&parsed[3..].join(" ")) {
+                                Ok(c) => {
+                                    io::print_log(&*format!(
+                                        "set breakpoint on {} if {}",
+                                        program.location_string(num),
+                                        c
+                                    ));
+                                    break_points.insert(num, Some(c));
+                                }
+                                Err(e) => io::print_error_str_no_exit(&e),
+                            }
+                        } else if break_points.remove(&num).is_none() {
+                            break_points.insert(num, None);
+                            io::print_log(&*format!(
+                                "set breakpoint on {}",
+                                program.location_string(num)
+                            ));
+                        } else {
+                            io::print_log(&*format!(
+                                "unset breakpoint on {}",
+                                program.location_string(num)
+                            ));
+                        }
+                    }
+
+                    "input" | "i" => {
+                        if parsed.len() < 2 {
+                            for s in ipt.get_all() {
+                                println!("{:?}", s);
+                            }
+                            continue;
+                        }
+                        let text = input.trim_start()[parsed[0].len()..]
+                            .trim_start()
+                            .trim_end_matches(['\n', '\r']);
+                        ipt.push(&*format!("{}\n", text));
+                        io::print_log(&*format!("queued input {:?}", text));
+                    }
+
+                    "info" => match info(&history, &program, &parsed) {
+                        Ok(t) => print!("{}", t),
+                        Err(e) => io::print_error_str_no_exit(&e),
+                    },
+
+                    "push" | "pop" | "put" | "cur" | "point" | "latest" => {
+                        match history.edit(&program, &parsed, ipt.position()) {
+                            Ok(t) => io::print_log(&t),
+                            Err(e) => io::print_error_str_no_exit(&e),
+                        }
+                    }
+
+                    "watch" | "w" => {
+                        if parsed.len() < 2 {
+                            let mut v = watch_points.iter().collect::<Vec<_>>();
+                            v.sort_by_key(|&&x| program.stacks.dot(x));
                             for i in v {
-                                println!("{}: {}", i, code[*i].get_raw());
+                                println!("stack {}", program.stacks.name(*i));
                             }
                             continue;
                         }
@@ -161,33 +1730,70 @@ pub fn run(code: Vec<UnOptCode>, from: usize) -> ! {
                                 continue;
                             }
                         };
-                        if num > code.len() {
-                            io::print_error_str_no_exit("number exceeds the range");
-                            continue;
-                        }
+                        let id = match program.stacks.id(num) {
+                            Ok(t) => t,
+                            Err(e) => {
+                                io::print_error_str_no_exit(&e);
+                                continue;
+                            }
+                        };
 
-                        if !break_points.contains(&num) {
-                            break_points.insert(num);
-                            io::print_log(&*format!("set breakpoint on line {}", num));
+                        if watch_points.insert(id) {
+                            io::print_log(&*format!("set watchpoint on stack {}", num));
                         } else {
-                            break_points.remove(&num);
-                            io::print_log(&*format!("unset breakpoint on line {}", num));
+                            watch_points.remove(&id);
+                            io::print_log(&*format!("unset watchpoint on stack {}", num));
                         }
                     }
 
                     "help" | "h" => {
-                        println!("break(b)       show breakpoints");
-                        println!("break(b) NUM   set/unset breakpoint on NUM");
-                        println!("exit           Exit debugger");
-                        println!("help(h)        Print this");
-                        println!("next(n)        goto next command");
-                        println!("state(s)       print state status");
-                        println!("previous(p)    move to previous state");
-                        println!("run(r)         run until breakpoint");
+                        println!("back N               move back N steps");
+                        println!("break(b)             show breakpoints");
+                        println!("break(b) LOC         set/unset breakpoint on LOC");
+                        println!("break(b) LOC if EXPR set breakpoint on LOC with condition");
+                        println!(
+                            "break(b) if EXPR     set/unset breakpoint when EXPR becomes true"
+                        );
+                        println!("                     LOC: LINE, LINE:COL, FILE:LINE or #NUM");
+                        println!("                     EXPR: e.g. top(5) == 0 && len(3) > 100 || cur == 7 || step >= 10");
+                        println!("cur STACK            set current stack");
+                        println!("exit                 Exit debugger");
+                        println!(
+                            "finish(f)            run until ♡ returns to latest location or its loop exits"
+                        );
+                        println!("goto STEP            move to STEP");
+                        println!("help(h)              Print this");
+                        println!("info jumps [N]       show last N jumps (10 by default)");
+                        println!("info points          show heart points and latest location");
+                        println!("input(i)             show queued program input");
+                        println!("input(i) TEXT        queue TEXT as a line of program input");
+                        println!("latest LOC           set latest location used by ♡");
+                        println!("next(n)              goto next command");
+                        println!("over(o)              run until next command of current one");
+                        println!("point HEART          clear point of HEART");
+                        println!("point HEART LOC      set point of HEART to LOC");
+                        println!("                     HEART: area count and heart, e.g. 3💕");
+                        println!("pop STACK            pop value from STACK");
+                        println!("push STACK VALUE     push VALUE to STACK");
+                        println!("put STACK IDX VALUE  overwrite IDXth value from bottom of STACK");
+                        println!("state(s)             print state status");
+                        println!("previous(p)          move to previous state");
+                        println!("reverse(rr)          run backward until breakpoint");
+                        println!("run(r)               run until breakpoint");
+                        println!("watch(w)             show watchpoints");
+                        println!("watch(w) NUM         set/unset watchpoint on stack NUM");
+                        if screen.is_some() {
+                            println!();
+                            for (k, _, name) in KEYS.iter() {
+                                println!("key {}                {}", k, name);
+                            }
+                            println!("key :                type a command above");
+                        }
                         continue;
                     }
 
                     "exit" => {
+                        close(&history.state, len);
                         process::exit(0);
                     }
 
@@ -203,6 +1809,7 @@ pub fn run(code: Vec<UnOptCode>, from: usize) -> ! {
         }
     }
 
+    close(&history.state, len);
     out.flush().unwrap();
     err.flush().unwrap();
 
@@ -0,0 +1,265 @@
+use crate::io;
+use std::fs::{self, OpenOptions};
+use std::io::{stdin, stdout, IsTerminal, Read, Write};
+use std::path::Path;
+
+/// Maximum number of lines kept in history
+const MAX_HISTORY: usize = 1000;
+
+/// Line editor for the interpreter
+///
+/// It moves cursor with arrow keys, `Ctrl-A`, `Ctrl-E`, `Ctrl-B`, `Ctrl-F`,
+/// deletes with `Backspace`, `Delete`, `Ctrl-K`, `Ctrl-U`,
+/// and finds history with up and down keys, `Ctrl-P`, `Ctrl-N`.
+/// `Ctrl-C` cancels the line and `Ctrl-D` on empty line ends input.
+/// When stdin is not a terminal, it just reads a line.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::editor::Editor;
+///
+/// let mut a = Editor::new(None);
+/// a.add_history("형.");
+/// a.add_history("형.");
+/// a.add_history("  ");
+/// assert_eq!(vec!["형.".to_string()], a.get_history());
+/// ```
+pub struct Editor {
+    history: Vec<String>,
+    file: Option<String>,
+}
+
+impl Editor {
+    /// Make new editor with history read from `file`
+    pub fn new(file: Option<String>) -> Editor {
+        let mut history = file
+            .as_ref()
+            .and_then(|x| fs::read_to_string(x).ok())
+            .map(|x| x.lines().map(|x| x.to_string()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        let skip = history.len().saturating_sub(MAX_HISTORY);
+        history.drain(..skip);
+        Editor { history, file }
+    }
+
+    /// Return history from the oldest
+    pub fn get_history(&self) -> Vec<String> {
+        self.history.clone()
+    }
+
+    /// Add line to history and append it to history file
+    /// Lines are joined with space since whitespace does nothing in code.
+    /// Empty line or same line as the last one is not added.
+    pub fn add_history(&mut self, line: &str) {
+        let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+        if line.is_empty() || self.history.last() == Some(&line) {
+            return;
+        }
+        self.history.push(line.clone());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+
+        if let Some(file) = &self.file {
+            if let Some(dir) = Path::new(file).parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(file) {
+                let _ = writeln!(f, "{}", line);
+            }
+        }
+    }
+
+    /// Read a line after printing `prompt`
+    /// Returns line with `\n` at the end, or empty string at the end of input like `io::read_line`.
+    #[cfg_attr(tarpaulin, skip)]
+    pub fn read_line(&mut self, prompt: &str) -> String {
+        print!("{}", prompt);
+        io::handle_error(stdout().flush());
+
+        if !stdin().is_terminal() {
+            return io::read_line();
+        }
+        let saved = match io::stty(&["-g"]) {
+            Some(t) => t,
+            None => return io::read_line(),
+        };
+        io::stty(&["-icanon", "-echo", "-isig", "min", "1"]);
+        let res = self.edit(prompt);
+        io::stty(&[&*saved]);
+        res
+    }
+
+    /// Read keys and edit line until enter
+    #[cfg_attr(tarpaulin, skip)]
+    fn edit(&mut self, prompt: &str) -> String {
+        let mut line: Vec<char> = Vec::new();
+        let mut pos = 0;
+        // index of history being shown, `history.len()` for the new line
+        let mut idx = self.history.len();
+        let mut new_line = Vec::new();
+
+        loop {
+            let key = match read_key() {
+                Some(t) => t,
+                None => return String::new(),
+            };
+
+            match key {
+                Key::Char(c) => {
+                    line.insert(pos, c);
+                    pos += 1;
+                }
+                Key::Enter => {
+                    print!("\r\n");
+                    return line.iter().collect::<String>() + "\n";
+                }
+                Key::Back if pos > 0 => {
+                    pos -= 1;
+                    line.remove(pos);
+                }
+                Key::Delete if pos < line.len() => {
+                    line.remove(pos);
+                }
+                Key::Left if pos > 0 => pos -= 1,
+                Key::Right if pos < line.len() => pos += 1,
+                Key::Home => pos = 0,
+                Key::End => pos = line.len(),
+                Key::KillEnd => line.truncate(pos),
+                Key::KillStart => {
+                    line.drain(..pos);
+                    pos = 0;
+                }
+                Key::Up | Key::Down => {
+                    if idx == self.history.len() {
+                        new_line = line.clone();
+                    }
+                    idx = match key {
+                        Key::Up => idx.saturating_sub(1),
+                        _ => (idx + 1).min(self.history.len()),
+                    };
+                    line = match self.history.get(idx) {
+                        Some(t) => t.chars().collect(),
+                        None => new_line.clone(),
+                    };
+                    pos = line.len();
+                }
+                Key::Cancel => {
+                    print!("^C\r\n{}", prompt);
+                    line.clear();
+                    pos = 0;
+                    idx = self.history.len();
+                }
+                Key::Eof if line.is_empty() => {
+                    print!("\r\n");
+                    return String::new();
+                }
+                Key::Eof if pos < line.len() => {
+                    line.remove(pos);
+                }
+                _ => {}
+            }
+
+            // redraw line and put cursor on `pos`
+            let back = line[pos..]
+                .iter()
+                .map(|&c| io::char_width(c))
+                .sum::<usize>();
+            print!("\r{}{}\x1b[K", prompt, line.iter().collect::<String>());
+            if back > 0 {
+                print!("\x1b[{}D", back);
+            }
+            io::handle_error(stdout().flush());
+        }
+    }
+}
+
+/// Key of line editor
+enum Key {
+    Char(char),
+    Enter,
+    Back,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillEnd,
+    KillStart,
+    Cancel,
+    Eof,
+    Unknown,
+}
+
+/// Read a byte from stdin
+#[cfg_attr(tarpaulin, skip)]
+fn read_byte() -> Option<u8> {
+    let mut buf = [0u8; 1];
+    match stdin().lock().read(&mut buf) {
+        Ok(1) => Some(buf[0]),
+        _ => None,
+    }
+}
+
+/// Read a key from stdin in raw mode
+#[cfg_attr(tarpaulin, skip)]
+fn read_key() -> Option<Key> {
+    let b = read_byte()?;
+    Some(match b {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Back,
+        0x01 => Key::Home,
+        0x02 => Key::Left,
+        0x03 => Key::Cancel,
+        0x04 => Key::Eof,
+        0x05 => Key::End,
+        0x06 => Key::Right,
+        0x0b => Key::KillEnd,
+        0x0e => Key::Down,
+        0x10 => Key::Up,
+        0x15 => Key::KillStart,
+        0x1b => match (read_byte()?, read_byte()?) {
+            (b'[', b'A') | (b'O', b'A') => Key::Up,
+            (b'[', b'B') | (b'O', b'B') => Key::Down,
+            (b'[', b'C') | (b'O', b'C') => Key::Right,
+            (b'[', b'D') | (b'O', b'D') => Key::Left,
+            (b'[', b'H') | (b'O', b'H') => Key::Home,
+            (b'[', b'F') | (b'O', b'F') => Key::End,
+            (b'[', t) if t.is_ascii_digit() => {
+                // like `ESC [ 3 ~`
+                let mut last = read_byte()?;
+                while last.is_ascii_digit() || last == b';' {
+                    last = read_byte()?;
+                }
+                match (t, last) {
+                    (b'3', b'~') => Key::Delete,
+                    (b'1', b'~') | (b'7', b'~') => Key::Home,
+                    (b'4', b'~') | (b'8', b'~') => Key::End,
+                    _ => Key::Unknown,
+                }
+            }
+            _ => Key::Unknown,
+        },
+        b if b < 0x20 => Key::Unknown,
+        b => {
+            // utf-8 sequence
+            let len = match b {
+                0xf0..=0xff => 4,
+                0xe0..=0xef => 3,
+                0xc0..=0xdf => 2,
+                _ => 1,
+            };
+            let mut buf = vec![b];
+            for _ in 1..len {
+                buf.push(read_byte()?);
+            }
+            match String::from_utf8(buf).ok().and_then(|x| x.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Unknown,
+            }
+        }
+    })
+}
@@ -1,3 +1,4 @@
+use crate::area::Area;
 use crate::code::Code;
 use crate::io::ReadLine;
 use crate::number::Num;
@@ -80,6 +81,17 @@ pub fn pop_stack_wrap<T>(
     state: &mut T,
     idx: usize,
 ) -> Num
+where
+    T: State,
+{
+    match pop_or_exit(ipt, state, idx) {
+        Ok(n) => n,
+        Err(code) => exit(out, err, code),
+    }
+}
+
+/// Pop from stack like `pop_stack_wrap`, but return exit code instead of exiting
+fn pop_or_exit<T>(ipt: &mut impl ReadLine, state: &mut T, idx: usize) -> Result<Num, i32>
 where
     T: State,
 {
@@ -91,24 +103,69 @@ where
                     state.push_stack(0, Num::from_num(c as isize));
                 }
             }
-            state.pop_stack(0)
+            Ok(state.pop_stack(0))
         }
-        1 => {
-            out.flush().unwrap();
-            err.flush().unwrap();
-            process::exit(0);
-        }
-        2 => {
-            out.flush().unwrap();
-            err.flush().unwrap();
-            process::exit(1);
+        1 | 2 => Err(idx as i32 - 1),
+        _ => Ok(state.pop_stack(idx)),
+    }
+}
+
+/// Flush output and exit the process with `code`
+fn exit(out: &mut impl Write, err: &mut impl Write, code: i32) -> ! {
+    out.flush().unwrap();
+    err.flush().unwrap();
+    process::exit(code);
+}
+
+/// Return exit code if executing code of `cur_loc` terminates the program
+/// It terminates when it pops from stack no 1 or 2.
+/// This only predicts it, and output of the command before the pop is still written.
+/// Use `execute_one_no_exit` to run code without exiting the process.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{execute, parse};
+/// use hyeong::state::{UnOptState, State};
+///
+/// let mut s = UnOptState::new();
+/// for c in parse::parse("형. 흑.. 항.".to_string()) {
+///     s.push_code(c);
+/// }
+///
+/// assert_eq!(None, execute::exit_code(&s, 0));
+/// assert_eq!(None, execute::exit_code(&s, 1));
+/// s.set_current_stack(2);
+/// assert_eq!(Some(1), execute::exit_code(&s, 2));
+/// ```
+pub fn exit_code<T>(state: &T, cur_loc: usize) -> Option<i32>
+where
+    T: State,
+{
+    let code = state.get_code(cur_loc);
+    let mut cur_stack = state.current_stack();
+
+    if code.get_type() != 0 && (cur_stack == 1 || cur_stack == 2) {
+        return Option::Some(cur_stack as i32 - 1);
+    }
+
+    if code.get_type() == 5 {
+        cur_stack = code.get_dot_count();
+    }
+
+    match code.get_area() {
+        Area::Val { type_, .. } if *type_ <= 1 && (cur_stack == 1 || cur_stack == 2) => {
+            Option::Some(cur_stack as i32 - 1)
         }
-        _ => state.pop_stack(idx),
+        _ => Option::None,
     }
 }
 
 /// Executes only one line of code and return next position of code
 ///
+/// Heart jumps are resolved with `point` of the state, not with [jump table](../jump/struct.JumpTable.html).
+/// Code is pushed one by one here, so the whole program is not known to build the table.
+///
 /// # Examples
 ///
 /// ```
@@ -130,9 +187,47 @@ pub fn execute_one<T>(
     ipt: &mut impl ReadLine,
     out: &mut impl Write,
     err: &mut impl Write,
-    mut state: T,
+    state: T,
     cur_loc: usize,
 ) -> (T, usize)
+where
+    T: State,
+{
+    match execute_one_no_exit(ipt, out, err, state, cur_loc) {
+        (state, Ok(loc)) => (state, loc),
+        (_, Err(code)) => exit(out, err, code),
+    }
+}
+
+/// Execute like `execute_one` but return exit code instead of exiting the process
+/// It stops at the pop from stack no 1 or 2, so output written before it is kept.
+/// State is returned as it is at that pop.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{execute, parse};
+/// use hyeong::io::{CustomReader, CustomWriter};
+/// use hyeong::state::{UnOptState, State};
+///
+/// let mut a = CustomReader::new("".to_string());
+/// let mut b = CustomWriter::new(|_| Result::Ok(()));
+/// let mut c = CustomWriter::new(|_| Result::Ok(()));
+/// let mut s = UnOptState::new();
+/// let t = parse::parse("흑.?💕".to_string());
+/// s.push_code(t[0].clone());
+///
+/// let (_, res) = execute::execute_one_no_exit(&mut a, &mut b, &mut c, s, 0);
+/// assert_eq!(Err(0), res);
+/// assert_eq!("너무 커엇...", b.to_string());
+/// ```
+pub fn execute_one_no_exit<T>(
+    ipt: &mut impl ReadLine,
+    out: &mut impl Write,
+    err: &mut impl Write,
+    mut state: T,
+    cur_loc: usize,
+) -> (T, Result<usize, i32>)
 where
     T: State,
 {
@@ -153,14 +248,20 @@ where
         1 => {
             let mut n = Num::zero();
             for _ in 0..code.get_hangul_count() {
-                n += &pop_stack_wrap(ipt, out, err, &mut state, cur_stack);
+                n += &match pop_or_exit(ipt, &mut state, cur_stack) {
+                    Ok(x) => x,
+                    Err(t) => return (state, Err(t)),
+                };
             }
             push_stack_wrap(out, err, &mut state, code.get_dot_count(), n);
         }
         2 => {
             let mut n = Num::one();
             for _ in 0..code.get_hangul_count() {
-                n *= &pop_stack_wrap(ipt, out, err, &mut state, cur_stack);
+                n *= &match pop_or_exit(ipt, &mut state, cur_stack) {
+                    Ok(x) => x,
+                    Err(t) => return (state, Err(t)),
+                };
             }
             push_stack_wrap(out, err, &mut state, code.get_dot_count(), n);
         }
@@ -169,7 +270,10 @@ where
             let mut v = Vec::with_capacity(code.get_hangul_count());
 
             for _ in 0..code.get_hangul_count() {
-                v.push(pop_stack_wrap(ipt, out, err, &mut state, cur_stack));
+                v.push(match pop_or_exit(ipt, &mut state, cur_stack) {
+                    Ok(x) => x,
+                    Err(t) => return (state, Err(t)),
+                });
             }
 
             for mut x in v {
@@ -185,7 +289,10 @@ where
             let mut v = Vec::with_capacity(code.get_hangul_count());
 
             for _ in 0..code.get_hangul_count() {
-                v.push(pop_stack_wrap(ipt, out, err, &mut state, cur_stack));
+                v.push(match pop_or_exit(ipt, &mut state, cur_stack) {
+                    Ok(x) => x,
+                    Err(t) => return (state, Err(t)),
+                });
             }
 
             for mut x in v {
@@ -198,7 +305,10 @@ where
         }
         // 5
         _ => {
-            let n = pop_stack_wrap(ipt, out, err, &mut state, cur_stack);
+            let n = match pop_or_exit(ipt, &mut state, cur_stack) {
+                Ok(x) => x,
+                Err(t) => return (state, Err(t)),
+            };
             for _ in 0..code.get_hangul_count() {
                 push_stack_wrap(out, err, &mut state, code.get_dot_count(), n.clone());
             }
@@ -208,10 +318,21 @@ where
     }
 
     cur_stack = state.current_stack();
-    let area_type = area::calc(code.get_area(), code.get_area_count(), || {
-        Option::Some(pop_stack_wrap(ipt, out, err, &mut state, cur_stack))
-    })
-    .unwrap();
+    let mut exited = Option::None;
+    let area_type = match area::calc(
+        code.get_area(),
+        code.get_area_count(),
+        || match pop_or_exit(ipt, &mut state, cur_stack) {
+            Ok(n) => Option::Some(n),
+            Err(code) => {
+                exited = Option::Some(code);
+                Option::None
+            }
+        },
+    ) {
+        Some(t) => t,
+        None => return (state, Err(exited.unwrap())),
+    };
 
     if area_type != 0 {
         if area_type != 13 {
@@ -220,19 +341,71 @@ where
                 Some(value) => {
                     if cur_loc != value {
                         state.set_latest_loc(cur_loc);
-                        return (state, value);
+                        return (state, Ok(value));
                     }
                 }
                 None => state.set_point(id, cur_loc),
             }
         } else {
             if let Some(loc) = state.get_latest_loc() {
-                return (state, loc);
+                return (state, Ok(loc));
             }
         }
     }
 
-    (state, cur_loc + 1)
+    (state, Ok(cur_loc + 1))
+}
+
+/// Execute like `execute` but return exit code instead of exiting the process
+/// When the program exits, output before the exiting pop is written
+/// and state is kept as it is at that pop.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{execute, parse};
+/// use hyeong::io::{CustomReader, CustomWriter};
+/// use hyeong::state::{UnOptState, State};
+///
+/// let mut a = CustomReader::new("0".to_string());
+/// let mut b = CustomWriter::new(|_| Result::Ok(()));
+/// let mut c = CustomWriter::new(|_| Result::Ok(()));
+/// let mut s = UnOptState::new();
+/// let t = parse::parse("형... 흑. 항.".to_string());
+///
+/// for i in 0..2 {
+///     let (t, exit) = execute::execute_no_exit(&mut a, &mut b, &mut c, s, &t[i]);
+///     assert_eq!(None, exit);
+///     s = t;
+/// }
+/// let (mut s, exit) = execute::execute_no_exit(&mut a, &mut b, &mut c, s, &t[2]);
+/// assert_eq!(Some(0), exit);
+/// assert_eq!(1, s.current_stack());
+/// assert_eq!("3", s.get_stack(3)[0].to_string());
+/// ```
+pub fn execute_no_exit<T>(
+    ipt: &mut impl ReadLine,
+    out: &mut impl Write,
+    err: &mut impl Write,
+    mut state: T,
+    code: &T::CodeType,
+) -> (T, Option<i32>)
+where
+    T: State,
+{
+    let mut cur_loc = state.push_code((*code).clone());
+    let length = cur_loc + 1;
+
+    while cur_loc < length {
+        let (new_state, res) = execute_one_no_exit(ipt, out, err, state, cur_loc);
+        state = new_state;
+        match res {
+            Ok(t) => cur_loc = t,
+            Err(t) => return (state, Some(t)),
+        }
+    }
+
+    (state, None)
 }
 
 /// Execute from new code until needs new code or finish
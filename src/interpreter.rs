@@ -1,48 +1,193 @@
-use crate::state::UnOptState;
+use crate::code::UnOptCode;
+use crate::editor::Editor;
+use crate::io::ReadLine;
+use crate::state::{State, UnOptState};
 use crate::{execute, io, parse};
 use colored::Colorize;
-use std::io::{stdin, stdout, Write};
+use std::fs;
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::path::Path;
 use std::process;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Instant;
+
+/// Read and parse `file` without exiting on error
+fn load(file: &str) -> Result<Vec<UnOptCode>, String> {
+    match fs::read_to_string(file) {
+        Ok(t) => Ok(parse::parse(t)),
+        Err(e) => Err(format!("cannot read {}: {}", file, e)),
+    }
+}
+
+/// Save snapshot of `state` to `file`
+fn save(state: &UnOptState, file: &str) -> Result<(), String> {
+    fs::write(file, state.to_snapshot()).map_err(|e| format!("cannot write {}: {}", file, e))
+}
+
+/// Restore state from snapshot in `file`
+fn restore(file: &str) -> Result<UnOptState, String> {
+    match fs::read_to_string(file) {
+        Ok(t) => UnOptState::from_snapshot(&t),
+        Err(e) => Err(format!("cannot read {}: {}", file, e)),
+    }
+}
+
+/// Program input of interpreter
+/// It reads from the queue, and asks a line on terminal when the queue is empty if `ask` is true.
+struct Input {
+    queue: io::QueueReader,
+    ask: bool,
+}
+
+impl ReadLine for Input {
+    #[cfg_attr(tarpaulin, skip)]
+    fn read_line_(&mut self) -> String {
+        if self.queue.is_empty() && self.ask {
+            print!("{} ", "program is waiting for input:".bright_yellow());
+            io::handle_error(stdout().flush());
+            self.queue.push(&io::read_line());
+        }
+        self.queue.read_line_()
+    }
+}
 
 /// Interpreter
 ///
 /// It gets code line by line and executes.
 /// Prints stdout and stderr separately.
+/// Lines starting with `:` are meta-commands below, and other lines are code.
+/// Code ending inside a hangul part continues to the next line, and a blank line cancels it.
+/// Lines are edited with [Editor](../editor/struct.Editor.html) and kept in history file.
+/// 1. `:diff`: toggle printing changes of state by each line
+/// 2. `:input`: print queued program input
+/// 3. `:input TEXT`: queue `TEXT` as a line of program input
+/// 4. `:load FILE`: run code of `FILE`
+/// 5. `:parse LINE`: print parsed commands of `LINE` without running
+/// 6. `:restore FILE`: restore state saved by `:save`
+/// 7. `:save FILE`: save state to `FILE`
+/// 8. `:stack N`: print stack `N`
+/// 9. `:state`: print current stack and stacks
+/// 10. `:time`: toggle printing time taken by each line
+/// 11. `:undo`: revert state and program input before the last line
+///
+/// Changes printed by `:diff` are made by [diff](../state/struct.UnOptState.html#method.diff).
+///
+/// Program reads input from `ipt` and the lines queued by `:input`,
+/// and it asks on terminal when there is nothing to read.
+///
+/// When `raw` is true or stdin is not a terminal, it reads lines without banner, prompts and logs.
+/// Program output goes straight to stdout and stderr, and errors go to stderr.
+/// Program input is only from `ipt` and `:input` in this mode.
+///
+/// It starts with `state`, and popping stack 1 or 2 stops the line without exiting.
+/// If `session` is given, state is restored from it if it exists, and saved to it on exit.
+/// Files are written by [to_snapshot](../state/struct.UnOptState.html#method.to_snapshot).
 #[cfg_attr(tarpaulin, skip)]
-pub fn run() -> ! {
+pub fn run(state: UnOptState, session: Option<&str>, ipt: io::QueueReader, raw: bool) -> ! {
     let running = Arc::new(AtomicBool::new(true));
     let r = running.clone();
-    let mut state = UnOptState::new();
-
-    ctrlc::set_handler(move || {
-        if r.load(Ordering::SeqCst) {
-            r.store(false, Ordering::SeqCst);
-            print!("\ntype \"흑.하앙...\" or \"exit\" to exit\n");
-            print!("{} ", ">".bright_blue());
-            io::handle_error(stdout().flush());
-            r.store(true, Ordering::SeqCst);
+    let raw = raw || !stdin().is_terminal();
+    if raw {
+        colored::control::set_override(false);
+    }
+    let log = |msg: &str| {
+        if !raw {
+            io::print_log(msg);
+        }
+    };
+    let error = |msg: &str| {
+        if raw {
+            eprintln!("error: {}", msg);
+        } else {
+            io::print_error_str_no_exit(msg);
+        }
+    };
+    let mut state = match session {
+        Some(t) if Path::new(t).exists() => match restore(t) {
+            Ok(s) => {
+                log(&*format!("restored session from {}", t));
+                s
+            }
+            Err(e) => io::print_error_string(&e),
+        },
+        _ => state,
+    };
+    let exit = |state: &UnOptState| -> ! {
+        if let Some(t) = session {
+            match save(state, t) {
+                Ok(()) => log(&*format!("saved session to {}", t)),
+                Err(e) => io::print_error_string(&e),
+            }
         }
-    })
-    .expect("Error setting Ctrl-C handler");
+        process::exit(0);
+    };
+    // states before each line for `:undo`
+    let mut history: Vec<(UnOptState, usize)> = Vec::new();
+    let mut ipt = Input {
+        queue: ipt,
+        ask: !raw,
+    };
+    let mut timing = false;
+    let mut diff = false;
 
-    println!("Hyeo-ung Programming Language");
-    println!("type help for help");
+    if !raw {
+        ctrlc::set_handler(move || {
+            if r.load(Ordering::SeqCst) {
+                r.store(false, Ordering::SeqCst);
+                print!("\ntype \"exit\" to exit\n");
+                print!("{} ", ">".bright_blue());
+                io::handle_error(stdout().flush());
+                r.store(true, Ordering::SeqCst);
+            }
+        })
+        .expect("Error setting Ctrl-C handler");
+
+        println!("Hyeo-ung Programming Language");
+        println!("type help for help");
+    }
+
+    let mut editor = Editor::new(if raw {
+        None
+    } else {
+        Some(io::get_history_path())
+    });
+    let prompt = |s: &str| {
+        if raw {
+            String::new()
+        } else {
+            format!("{} ", s.bright_blue())
+        }
+    };
 
     loop {
-        print!("{} ", ">".bright_blue());
-        io::handle_error(stdout().flush());
         running.store(true, Ordering::SeqCst);
-        let input = io::read_line();
+        let mut input = editor.read_line(&prompt(">"));
+        // code can continue to next line inside hangul part
+        while !input.trim_start().starts_with(':') && parse::is_incomplete(&input) {
+            let next = editor.read_line(&prompt("."));
+            if next.is_empty() {
+                break;
+            }
+            // blank line cancels the incomplete line
+            if next.trim().is_empty() {
+                input = next;
+                break;
+            }
+            input.push_str(&next);
+        }
+        editor.add_history(&input);
         running.store(false, Ordering::SeqCst);
 
-        if input == "" {
-            process::exit(0);
+        if input.is_empty() {
+            exit(&state);
         }
 
         let mut out = io::CustomWriter::new(|x| {
-            if !x.is_empty() {
+            if raw {
+                print!("{}", x);
+                stdout().flush()?;
+            } else if !x.is_empty() {
                 println!("[{}] {}", "stdout".bold(), x);
             }
 
@@ -50,43 +195,174 @@ pub fn run() -> ! {
         });
 
         let mut err = io::CustomWriter::new(|x| {
-            if !x.is_empty() {
+            if raw {
+                eprint!("{}", x);
+            } else if !x.is_empty() {
                 println!("[{}] {}", "stderr".bold().bright_red(), x);
             }
 
             Result::Ok(())
         });
 
-        match input.trim() {
+        let line = input.trim();
+        let (cmd, arg) = match line.find(' ') {
+            Some(i) if line.starts_with(':') => (&line[..i], line[i..].trim()),
+            _ => (line, ""),
+        };
+
+        let code = match cmd {
             "" => {
                 continue;
             }
 
             "clear" => {
+                history.push((state, ipt.queue.position()));
                 state = UnOptState::new();
+                continue;
             }
 
             "help" => {
-                println!("clear  Clears the state");
-                println!("exit   Exit this interpreter");
-                println!("       You can also exit by typing \"흑.하앙...\"");
-                println!("help   Print this");
+                println!("clear          Clears the state");
+                println!("exit           Exit this interpreter");
+                println!("               Code popping stack 1 or 2 like \"흑.하앙...\" only stops the line");
+                println!("help           Print this");
+                println!(":diff          Toggle printing changes of state by each line");
+                println!(":input         Print queued program input");
+                println!(":input TEXT    Queue TEXT as a line of program input");
+                println!(":load FILE     Run code of FILE");
+                println!(":parse LINE    Print parsed commands of LINE without running");
+                println!(":restore FILE  Restore state saved by :save");
+                println!(":save FILE     Save state to FILE");
+                println!(":stack N       Print stack N");
+                println!(":state         Print current stack and stacks");
+                println!(":time          Toggle printing time taken by each line");
+                println!(":undo          Revert state and program input before the last line");
                 continue;
             }
 
             "exit" => {
-                process::exit(0);
+                exit(&state);
+            }
+
+            ":diff" => {
+                diff = !diff;
+                log(if diff { "diff on" } else { "diff off" });
+                continue;
             }
 
-            _ => {
-                let code = parse::parse(input);
-                for c in code.iter() {
-                    state = execute::execute(&mut stdin(), &mut out, &mut err, state, c);
+            ":input" => {
+                if arg.is_empty() {
+                    for s in ipt.queue.get_all() {
+                        println!("{:?}", s);
+                    }
+                } else {
+                    ipt.queue.push(&*format!("{}\n", arg));
+                    log(&*format!("queued input {:?}", arg));
                 }
+                continue;
+            }
+
+            ":load" => match load(arg) {
+                Ok(t) => t,
+                Err(e) => {
+                    error(&e);
+                    continue;
+                }
+            },
+
+            ":parse" => {
+                for c in parse::parse(arg.to_string()) {
+                    println!("{}", c.to_string());
+                }
+                continue;
+            }
+
+            ":restore" => {
+                match restore(arg) {
+                    Ok(t) => {
+                        history.push((std::mem::replace(&mut state, t), ipt.queue.position()));
+                        log(&*format!("restored state from {}", arg));
+                    }
+                    Err(e) => error(&e),
+                }
+                continue;
+            }
+
+            ":save" => {
+                match save(&state, arg) {
+                    Ok(()) => log(&*format!("saved state to {}", arg)),
+                    Err(e) => error(&e),
+                }
+                continue;
+            }
+
+            ":stack" => {
+                match arg.parse::<usize>() {
+                    Ok(n) if state.get_all_stack_index().contains(&n) => {
+                        println!("stack {}: {:?}", n, state.get_stack(n));
+                    }
+                    Ok(n) => println!("stack {}: []", n),
+                    Err(_) => error("usage: :stack N"),
+                }
+                continue;
+            }
+
+            ":state" => {
+                print!("{:?}", state);
+                continue;
+            }
+
+            ":time" => {
+                timing = !timing;
+                log(if timing { "timing on" } else { "timing off" });
+                continue;
+            }
+
+            ":undo" => {
+                match history.pop() {
+                    Some((t, position)) => {
+                        state = t;
+                        ipt.queue.set_position(position);
+                        log("reverted the last line");
+                    }
+                    None => error("nothing to undo"),
+                }
+                continue;
+            }
+
+            t if t.starts_with(':') => {
+                error(&*format!("unknown command \"{}\", type help for help", t));
+                continue;
+            }
+
+            _ => parse::parse(input),
+        };
+
+        history.push((state.clone(), ipt.queue.position()));
+        let start = Instant::now();
+        for c in code.iter() {
+            let (t, exit) = execute::execute_no_exit(&mut ipt, &mut out, &mut err, state, c);
+            state = t;
+            if let Some(t) = exit {
+                out.flush().unwrap();
+                err.flush().unwrap();
+                log(&*format!("program exited with code {}", t));
+                break;
             }
         }
+        let time = start.elapsed();
 
         out.flush().unwrap();
         err.flush().unwrap();
+        if diff {
+            print!("{}", state.diff(&history.last().unwrap().0));
+        }
+        if timing {
+            if raw {
+                eprintln!("took {:?}", time);
+            } else {
+                io::print_log(&*format!("took {:?}", time));
+            }
+        }
     }
 }
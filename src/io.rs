@@ -4,7 +4,7 @@ use colored::Colorize;
 use std::error::Error;
 use std::fs::File;
 use std::io::{Read, Write};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::{env, process};
 
 /// Custom writer structure for redirecting output.
@@ -139,6 +139,86 @@ impl CustomReader {
     }
 }
 
+/// Queue of input lines for other input source
+/// Each line is read with `\n` at the end like `std::io::Stdin`.
+/// Lines that are read are kept, so reading can be rewound.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::io::{QueueReader, ReadLine};
+///
+/// let mut a = QueueReader::new();
+/// a.push("Hello,\nWorld!");
+///
+/// assert_eq!("Hello,\n", a.read_line_());
+/// assert_eq!("World!", a.read_line_());
+/// assert_eq!(true, a.is_empty());
+/// assert_eq!("", a.read_line_());
+///
+/// a.set_position(1);
+/// assert_eq!("World!", a.read_line_());
+/// ```
+pub struct QueueReader {
+    buf: Vec<String>,
+    idx: usize,
+}
+
+impl ReadLine for QueueReader {
+    /// Reads next line, empty string if every line is read
+    fn read_line_(&mut self) -> String {
+        if self.buf.len() == self.idx {
+            String::from("")
+        } else {
+            let res = self.buf[self.idx].clone();
+            self.idx += 1;
+            res
+        }
+    }
+}
+
+impl QueueReader {
+    /// Make new empty `QueueReader`
+    pub fn new() -> QueueReader {
+        QueueReader {
+            buf: Vec::new(),
+            idx: 0,
+        }
+    }
+
+    /// Push text to the end of queue
+    pub fn push(&mut self, s: &str) {
+        self.buf
+            .extend(s.split_inclusive('\n').map(|x| x.to_string()));
+    }
+
+    /// Return true if there is no line to read
+    pub fn is_empty(&self) -> bool {
+        self.buf.len() == self.idx
+    }
+
+    /// Return lines that are not read yet
+    pub fn get_all(&self) -> Vec<String> {
+        self.buf[self.idx..].to_vec()
+    }
+
+    /// Return number of lines read
+    pub fn position(&self) -> usize {
+        self.idx
+    }
+
+    /// Rewind or skip to `idx`th line
+    pub fn set_position(&mut self, idx: usize) {
+        self.idx = idx.min(self.buf.len());
+    }
+}
+
+impl Default for QueueReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Read `.hyeong` file and parse to code
 pub fn read_file(file: &str) -> Vec<UnOptCode> {
     if !check_file(file) {
@@ -150,6 +230,11 @@ pub fn read_file(file: &str) -> Vec<UnOptCode> {
     res
 }
 
+/// Read any file to string
+pub fn read_text(file: &str) -> String {
+    handle_error(read_file_base(file))
+}
+
 /// Base function of `read_file`
 /// It reads any file and return to string
 fn read_file_base(file: &str) -> Result<String, std::io::Error> {
@@ -241,6 +326,47 @@ pub fn print_note(msg: &str) {
     println!("[{}] {}", "note".bright_cyan(), msg);
 }
 
+/// Width of character on terminal
+/// Hangul and emoji take two columns.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::io;
+///
+/// assert_eq!(2, io::char_width('형'));
+/// assert_eq!(1, io::char_width('.'));
+/// ```
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f
+        | 0x2e80..=0xa4cf
+        | 0xac00..=0xd7a3
+        | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f
+        | 0xff00..=0xff60
+        | 0xffe0..=0xffe6
+        | 0x1f300..=0x1faff => 2,
+        _ => 1,
+    }
+}
+
+/// Run `stty` on the terminal of stdin and return its output
+/// Returns `None` if it fails, like when stdin is not a terminal.
+#[cfg_attr(tarpaulin, skip)]
+pub fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
 /// Write to `Write`
 ///
 /// # Examples
@@ -275,6 +401,17 @@ pub fn save_to_file(file: &str, content: String) {
     handle_error(save_file_base(file, content));
 }
 
+/// Get path of REPL history file
+/// Differ from os
+#[cfg_attr(tarpaulin, skip)]
+pub fn get_history_path() -> String {
+    if cfg!(target_os = "windows") {
+        env::var("USERPROFILE").unwrap_or_default() + "\\.hyeong\\history"
+    } else {
+        env::var("HOME").unwrap_or_default() + "/.hyeong/history"
+    }
+}
+
 /// Get build path
 /// Differ from os
 #[cfg_attr(tarpaulin, skip)]
@@ -325,4 +462,4 @@ pub fn execute_command(windows: &str, linux: &str) {
     } else {
         Command::new("bash").arg("-c").arg(linux).output();
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,194 @@
+use crate::area::Area;
+use crate::code::Code;
+use std::collections::{HashMap, HashSet};
+
+/// Jump target of a heart id
+///
+/// - `Static(loc)`: every jump of this id goes to `loc`
+/// - `Dynamic`: target depends on which command registers first at run time
+#[derive(Clone, Debug, PartialEq)]
+pub enum Jump {
+    Static(usize),
+    Dynamic,
+}
+
+/// Jump table made by [analyze](fn.analyze.html)
+///
+/// It maps heart id (`(area_count << 4) + heart`) to its jump target.
+/// `♡` is not in the table since it always goes to the latest location.
+///
+/// It is used by `build` codegen and the `liveness` and `peephole` passes, which see the whole program.
+/// `execute_one` and `prefix-eval` pass get code one by one, so they still use `point` of the state.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::jump::{self, Jump};
+/// use hyeong::parse;
+///
+/// let code = parse::parse("형. 형.💕 형. 형.💕".to_string());
+/// let table = jump::analyze(&code, 0, &[]);
+///
+/// assert_eq!(Some(&Jump::Static(1)), table.get((1 << 4) + 4));
+/// ```
+#[derive(Clone, Debug)]
+pub struct JumpTable {
+    table: HashMap<u128, Jump>,
+}
+
+impl JumpTable {
+    /// Return jump target of `id`
+    /// `None` if no command can make `id`
+    pub fn get(&self, id: u128) -> Option<&Jump> {
+        self.table.get(&id)
+    }
+
+    /// Return all ids with its jump targets sorted by id
+    pub fn get_all(&self) -> Vec<(u128, Jump)> {
+        let mut v = self
+            .table
+            .iter()
+            .map(|(a, b)| (*a, b.clone()))
+            .collect::<Vec<_>>();
+        v.sort_by_key(|x| x.0);
+        v
+    }
+
+    /// Map every static location with `f`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::jump::{self, Jump};
+    /// use hyeong::parse;
+    ///
+    /// let code = parse::parse("형.💕 형.💕".to_string());
+    /// let table = jump::analyze(&code, 0, &[]).map_location(|x| x + 10);
+    ///
+    /// assert_eq!(Some(&Jump::Static(10)), table.get((1 << 4) + 4));
+    /// ```
+    pub fn map_location<F>(&self, f: F) -> JumpTable
+    where
+        F: Fn(usize) -> usize,
+    {
+        JumpTable {
+            table: self
+                .table
+                .iter()
+                .map(|(a, b)| {
+                    (
+                        *a,
+                        match b {
+                            Jump::Static(loc) => Jump::Static(f(*loc)),
+                            Jump::Dynamic => Jump::Dynamic,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Collect every result that `area` can be evaluated to
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::code::Code;
+/// use hyeong::{jump, parse};
+/// use std::collections::HashSet;
+///
+/// let code = parse::parse("형.?💕!♥".to_string());
+/// let mut res = HashSet::new();
+/// jump::area_results(code[0].get_area(), &mut res);
+///
+/// assert_eq!(vec![0, 2, 4], {
+///     let mut v = res.into_iter().collect::<Vec<_>>();
+///     v.sort();
+///     v
+/// });
+/// ```
+pub fn area_results(area: &Area, res: &mut HashSet<u8>) {
+    match area {
+        Area::Val { type_, left, right } => {
+            if *type_ <= 1 {
+                area_results(left, res);
+                area_results(right, res);
+            } else {
+                res.insert(*type_);
+            }
+        }
+        Area::Nil => {
+            res.insert(0);
+        }
+    }
+}
+
+/// Builds jump table of heart ids
+///
+/// `code[..start]` is the code that has already been executed and
+/// `points` is the heart points registered by it.
+/// For plain program, `start` is `0` and `points` is empty.
+///
+/// # Algorithm
+///
+/// Heart point is registered only once by the first command that makes the id.
+/// Also, every jump goes to the location that is already visited,
+/// so command `i` is visited for the first time only after all commands before `i` are visited.
+///
+/// So, for each id,
+///
+/// 1. if the id is already in `points`, it is the target.
+/// 2. if only one command can make the id, the target is that command.
+///    (jumping to itself does nothing)
+/// 3. if the first command that can make the id is not executed yet
+///    and it always makes the id, the target is that command.
+/// 4. else, it is dynamic.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::jump::{self, Jump};
+/// use hyeong::parse;
+///
+/// let code = parse::parse("형.?💕 형.💕".to_string());
+/// let table = jump::analyze(&code, 0, &[]);
+///
+/// assert_eq!(Some(&Jump::Dynamic), table.get((1 << 4) + 4));
+/// ```
+pub fn analyze<T>(code: &[T], start: usize, points: &[(u128, usize)]) -> JumpTable
+where
+    T: Code,
+{
+    let mut candidates: HashMap<u128, Vec<(usize, bool)>> = HashMap::new();
+
+    for (i, c) in code.iter().enumerate() {
+        let mut res = HashSet::new();
+        area_results(c.get_area(), &mut res);
+        let always = res.len() == 1;
+
+        for t in res {
+            if t == 0 || t == 13 {
+                continue;
+            }
+            let id = ((c.get_area_count() as u128) << 4) + t as u128;
+            candidates.entry(id).or_default().push((i, always));
+        }
+    }
+
+    let registered = points.iter().cloned().collect::<HashMap<_, _>>();
+    let mut table = HashMap::new();
+
+    for (id, v) in candidates {
+        let jump = if let Some(loc) = registered.get(&id) {
+            Jump::Static(*loc)
+        } else if v.len() == 1 || (v[0].0 >= start && v[0].1) {
+            Jump::Static(v[0].0)
+        } else {
+            Jump::Dynamic
+        };
+        table.insert(id, jump);
+    }
+
+    JumpTable { table }
+}
@@ -2,11 +2,16 @@ pub mod area;
 pub mod big_number;
 pub mod build;
 pub mod code;
+pub mod dap;
 pub mod debug;
+pub mod editor;
 pub mod execute;
 pub mod interpreter;
 pub mod io;
+pub mod jump;
+pub mod liveness;
 pub mod number;
 pub mod optimize;
 pub mod parse;
+pub mod peephole;
 pub mod state;
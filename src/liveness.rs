@@ -0,0 +1,551 @@
+use crate::area::Area;
+use crate::code::Code;
+use crate::jump::{self, Jump};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Range of stack height in analysis
+/// `hi` is `None` if it is unbounded.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Height {
+    lo: usize,
+    hi: Option<usize>,
+}
+
+impl Height {
+    fn new(n: usize) -> Height {
+        Height { lo: n, hi: Some(n) }
+    }
+
+    fn join(self, other: Height) -> Height {
+        Height {
+            lo: self.lo.min(other.lo),
+            hi: match (self.hi, other.hi) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Join that makes `hi` unbounded if it grows, to end loops
+    fn widen(self, other: Height) -> Height {
+        let res = self.join(other);
+        if res.hi != self.hi {
+            Height {
+                lo: res.lo,
+                hi: None,
+            }
+        } else {
+            res
+        }
+    }
+
+    fn pop(self, count: usize) -> Height {
+        Height {
+            lo: self.lo.saturating_sub(count),
+            hi: self.hi.map(|x| x.saturating_sub(count)),
+        }
+    }
+
+    /// Pop values as many as unknown
+    fn pop_any(self) -> Height {
+        Height { lo: 0, hi: self.hi }
+    }
+
+    /// Push a value that can be `NaN`, which is not pushed to empty stack
+    fn push(self) -> Height {
+        Height {
+            lo: if self.lo == 0 { 0 } else { self.lo + 1 },
+            hi: self.hi.map(|x| x + 1),
+        }
+    }
+
+    /// Push a value that is `NaN`
+    fn push_nan(self) -> Height {
+        Height {
+            lo: if self.lo == 0 { 0 } else { self.lo + 1 },
+            hi: self.hi.map(|x| if x == 0 { 0 } else { x + 1 }),
+        }
+    }
+
+    /// Push a value that is never `NaN`
+    fn push_num(self) -> Height {
+        Height {
+            lo: self.lo + 1,
+            hi: self.hi.map(|x| x + 1),
+        }
+    }
+
+    fn is_empty(self) -> bool {
+        self.hi == Some(0)
+    }
+}
+
+/// Number of values that command pops from current stack
+fn pop_count<T: Code>(c: &T) -> usize {
+    match c.get_type() {
+        0 => 0,
+        5 => 1,
+        _ => c.get_hangul_count(),
+    }
+}
+
+/// Whether area pops values from current stack
+fn area_pops(area: &Area) -> bool {
+    match area {
+        Area::Val { type_, .. } => *type_ <= 1,
+        Area::Nil => false,
+    }
+}
+
+/// Locations that can come after each command
+///
+/// Jump targets are from [jump::analyze](../jump/fn.analyze.html).
+/// Dynamic jump goes to every command that can make the id,
+/// and `♡` goes to every command that can jump.
+fn successors<T: Code>(
+    code: &[T],
+    start: usize,
+    points: &[(u128, usize)],
+    latest: Option<usize>,
+) -> Vec<Vec<usize>> {
+    let table = jump::analyze(code, start, points);
+    let results = code
+        .iter()
+        .map(|c| {
+            let mut res = HashSet::new();
+            jump::area_results(c.get_area(), &mut res);
+            res
+        })
+        .collect::<Vec<_>>();
+
+    let mut makers: HashMap<u128, Vec<usize>> = HashMap::new();
+    let mut sources = latest.into_iter().collect::<Vec<_>>();
+    for (i, res) in results.iter().enumerate() {
+        for &t in res {
+            if t != 0 && t != 13 {
+                let id = ((code[i].get_area_count() as u128) << 4) + t as u128;
+                makers.entry(id).or_default().push(i);
+            }
+        }
+        if res.iter().any(|&t| t != 0 && t != 13) {
+            sources.push(i);
+        }
+    }
+
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, res)| {
+            let mut next = vec![i + 1];
+            for &t in res {
+                if t == 13 {
+                    next.extend(&sources);
+                } else if t != 0 {
+                    let id = ((code[i].get_area_count() as u128) << 4) + t as u128;
+                    match table.get(id) {
+                        Some(Jump::Static(loc)) => next.push(*loc),
+                        _ => next.extend(&makers[&id]),
+                    }
+                }
+            }
+            next.sort_unstable();
+            next.dedup();
+            next
+        })
+        .collect()
+}
+
+/// Stack liveness of code
+///
+/// It is made by [analyze](fn.analyze.html).
+/// Only stacks larger than 3 are analyzed,
+/// since stack 0 to 3 are input, output, error and the first current stack.
+#[derive(Debug)]
+pub struct Liveness {
+    // locations after each command
+    next: Vec<Vec<usize>>,
+    // stacks that can be current before each command
+    cur: Vec<BTreeSet<usize>>,
+    // stacks that can be read after each command pushes
+    live_mid: Vec<HashSet<usize>>,
+    // stacks that can be read before each command
+    live_in: Vec<HashSet<usize>>,
+}
+
+impl Liveness {
+    /// Whether command at `loc` can be executed
+    pub fn is_reachable(&self, loc: usize) -> bool {
+        !self.cur[loc].is_empty()
+    }
+
+    /// Stacks that can be current before command at `loc`
+    pub fn current_stacks(&self, loc: usize) -> Vec<usize> {
+        self.cur[loc].iter().copied().collect()
+    }
+
+    /// Whether stack `idx` can be read after command at `loc` pushes
+    /// Pushes to stack that is not live are never read.
+    pub fn is_live_after(&self, loc: usize, idx: usize) -> bool {
+        idx <= 3 || self.live_mid[loc].contains(&idx)
+    }
+
+    /// Stacks that can be read or be current somewhere
+    pub fn used_stacks(&self) -> HashSet<usize> {
+        self.live_in
+            .iter()
+            .flatten()
+            .chain(self.cur.iter().flatten())
+            .copied()
+            .filter(|&x| x > 3)
+            .collect()
+    }
+}
+
+/// Analyze stack liveness
+///
+/// `code[..start]` is the code that has already been executed,
+/// `cur`, `points` and `latest` are current stack, heart points and latest location after it.
+///
+/// # Algorithm
+///
+/// Every jump goes to the location that is already visited,
+/// so the targets are from heart points and commands that can make heart.
+///
+/// 1. Stacks that can be current are propagated forward from `start`.
+/// 2. Stack is read when it is popped as current stack.
+/// 3. Stack is live if it can be read later, and it is propagated backward.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{liveness, parse};
+///
+/// let code = parse::parse("형. 항.... 흑..... 항...... 항.".to_string());
+/// let res = liveness::analyze(&code, 0, 3, &[], None);
+///
+/// assert!(!res.is_live_after(1, 4));
+/// assert!(res.is_live_after(2, 5));
+/// assert!(!res.is_live_after(3, 6));
+/// ```
+pub fn analyze<T: Code>(
+    code: &[T],
+    start: usize,
+    cur: usize,
+    points: &[(u128, usize)],
+    latest: Option<usize>,
+) -> Liveness {
+    let n = code.len();
+    let next = successors(code, start, points, latest);
+
+    let mut cur_set = vec![BTreeSet::new(); n + 1];
+    cur_set[start].insert(cur);
+    let mut work = vec![start];
+    while let Some(i) = work.pop() {
+        if i >= n {
+            continue;
+        }
+        let out = if code[i].get_type() == 5 {
+            std::iter::once(code[i].get_dot_count()).collect()
+        } else {
+            cur_set[i].clone()
+        };
+        for &j in &next[i] {
+            let len = cur_set[j].len();
+            cur_set[j].extend(&out);
+            if cur_set[j].len() != len {
+                work.push(j);
+            }
+        }
+    }
+
+    let mut live_mid = vec![HashSet::new(); n];
+    let mut live_in = vec![HashSet::new(); n + 1];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in (0..n).rev() {
+            if cur_set[i].is_empty() {
+                continue;
+            }
+            let mut mid = next[i]
+                .iter()
+                .flat_map(|&j| live_in[j].iter().copied())
+                .collect::<HashSet<_>>();
+            if area_pops(code[i].get_area()) {
+                if code[i].get_type() == 5 {
+                    mid.insert(code[i].get_dot_count());
+                } else {
+                    mid.extend(&cur_set[i]);
+                }
+            }
+            mid.retain(|&x| x > 3);
+
+            let mut live = mid.clone();
+            if pop_count(&code[i]) > 0 {
+                live.extend(cur_set[i].iter().filter(|&&x| x > 3));
+            }
+
+            if live.len() != live_in[i].len() || mid.len() != live_mid[i].len() {
+                changed = true;
+                live_in[i] = live;
+                live_mid[i] = mid;
+            }
+        }
+    }
+
+    Liveness {
+        next,
+        cur: cur_set,
+        live_mid,
+        live_in,
+    }
+}
+
+/// Stacks that can not share one index with each stack
+///
+/// Two stacks interfere if both can be non-empty at the same time,
+/// or one can be non-empty when the other is read.
+/// `dots` is the stack of each command after dead pushes are removed,
+/// and `heights` is the height of stacks before `start`.
+fn interference<T: Code>(
+    code: &[T],
+    dots: &[usize],
+    start: usize,
+    heights: &HashMap<usize, usize>,
+    live: &Liveness,
+    stacks: &[usize],
+) -> Vec<HashSet<usize>> {
+    let n = code.len();
+    let id = stacks
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (*x, i))
+        .collect::<HashMap<_, _>>();
+    let mut edge = vec![HashSet::new(); stacks.len()];
+    let mut add = |h: &[Height], read: &HashSet<usize>| {
+        let v = (0..stacks.len())
+            .filter(|i| !h[*i].is_empty() || read.contains(i))
+            .collect::<Vec<_>>();
+        for &a in &v {
+            for &b in &v {
+                if a != b {
+                    edge[a].insert(b);
+                }
+            }
+        }
+    };
+
+    let mut height_in: Vec<Option<Vec<Height>>> = vec![None; n + 1];
+    height_in[start] = Some(
+        stacks
+            .iter()
+            .map(|x| Height::new(*heights.get(x).unwrap_or(&0)))
+            .collect(),
+    );
+    let mut work = vec![start];
+    while let Some(i) = work.pop() {
+        if i >= n {
+            continue;
+        }
+        let h = height_in[i].clone().unwrap();
+        let c = &code[i];
+        let count = pop_count(c);
+        let dot = id.get(&dots[i]).copied();
+        let mut read_in = HashSet::new();
+        let mut read_mid = HashSet::new();
+        let mut mid: Option<Vec<Height>> = None;
+        let mut out: Option<Vec<Height>> = None;
+
+        for &now in &live.cur[i] {
+            let now = id.get(&now).copied();
+            let mut t = h.clone();
+            // whether popped value is `NaN` for sure
+            let nan = match now {
+                Some(x) => {
+                    if count > 0 {
+                        read_in.insert(x);
+                    }
+                    let nan = t[x].hi.is_some_and(|y| y < count);
+                    t[x] = t[x].pop(count);
+                    nan
+                }
+                None => false,
+            };
+            let push = |x: Height| if nan { x.push_nan() } else { x.push() };
+            match c.get_type() {
+                0 => {
+                    if let Some(x) = now {
+                        t[x] = t[x].push_num();
+                    }
+                }
+                1 | 2 => {
+                    if let Some(x) = dot {
+                        t[x] = if count == 0 {
+                            t[x].push_num()
+                        } else {
+                            push(t[x])
+                        };
+                    }
+                }
+                3 | 4 => {
+                    if let Some(x) = now {
+                        for _ in 0..count {
+                            t[x] = t[x].push();
+                        }
+                    }
+                    if let Some(x) = dot {
+                        t[x] = if count == 0 {
+                            t[x].push_num()
+                        } else {
+                            push(t[x])
+                        };
+                    }
+                }
+                _ => {
+                    if let Some(x) = dot {
+                        for _ in 0..c.get_hangul_count() {
+                            t[x] = push(t[x]);
+                        }
+                    }
+                    if let Some(x) = now {
+                        t[x] = push(t[x]);
+                    }
+                }
+            }
+            mid = Some(match mid {
+                Some(m) => m.iter().zip(&t).map(|(a, b)| a.join(*b)).collect(),
+                None => t.clone(),
+            });
+
+            let after = if c.get_type() == 5 { dot } else { now };
+            if let Some(x) = after {
+                if area_pops(c.get_area()) {
+                    read_mid.insert(x);
+                    t[x] = t[x].pop_any();
+                }
+            }
+            out = Some(match out {
+                Some(m) => m.iter().zip(&t).map(|(a, b)| a.join(*b)).collect(),
+                None => t,
+            });
+        }
+
+        add(&h, &read_in);
+        add(&mid.unwrap(), &read_mid);
+        let out = out.unwrap();
+        for &j in &live.next[i] {
+            let new = match &height_in[j] {
+                Some(m) => m.iter().zip(&out).map(|(a, b)| a.widen(*b)).collect(),
+                None => out.clone(),
+            };
+            if height_in[j].as_ref() != Some(&new) {
+                height_in[j] = Some(new);
+                work.push(j);
+            }
+        }
+    }
+
+    edge
+}
+
+/// Result of stack allocation by [allocate](fn.allocate.html)
+#[derive(Debug)]
+pub struct Allocation {
+    /// Stack of each command
+    /// Pushes to stack `size` are removed since it is out of range.
+    pub dots: Vec<usize>,
+    /// New index of stacks that are used
+    pub ids: HashMap<usize, usize>,
+    /// Number of stacks
+    pub size: usize,
+}
+
+/// Allocate stacks with liveness
+///
+/// Arguments are the same with [analyze](fn.analyze.html),
+/// and `heights` is the number of values in each stack before `start`.
+///
+/// 1. Pushes to stacks that are not live are removed, except `흑` that changes current stack.
+/// 2. Two stacks interfere if both can be non-empty at the same time,
+///    or one can be non-empty when the other is read.
+/// 3. Stacks that do not interfere share one index, so stacks are colored greedily.
+///
+/// Stack 0 to 3 are not changed.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{liveness, parse};
+/// use std::collections::HashMap;
+///
+/// let code = parse::parse("형. 흑.... 항. 흑..... 형.. 항. 항......".to_string());
+/// let res = liveness::allocate(&code, 0, 3, &[], None, &HashMap::new());
+///
+/// assert_eq!(5, res.size);
+/// assert_eq!(4, res.ids[&4]);
+/// assert_eq!(4, res.ids[&5]);
+/// assert_eq!(vec![1, 4, 1, 4, 2, 1, 5], res.dots);
+/// ```
+pub fn allocate<T: Code>(
+    code: &[T],
+    start: usize,
+    cur: usize,
+    points: &[(u128, usize)],
+    latest: Option<usize>,
+    heights: &HashMap<usize, usize>,
+) -> Allocation {
+    let live = analyze(code, start, cur, points, latest);
+    let used = live.used_stacks();
+    let mut stacks = used.iter().copied().collect::<Vec<_>>();
+    stacks.sort_unstable();
+
+    let mut dots = code
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let d = c.get_dot_count();
+            if c.get_type() == 0 || d <= 3 {
+                d
+            } else if !used.contains(&d)
+                || (c.get_type() != 5 && live.is_reachable(i) && !live.is_live_after(i, d))
+            {
+                usize::MAX
+            } else {
+                d
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let heights = heights
+        .iter()
+        .filter(|(x, _)| used.contains(x))
+        .map(|(x, h)| (*x, *h))
+        .collect();
+    let edge = interference(code, &dots, start, &heights, &live, &stacks);
+
+    let mut color = vec![0; stacks.len()];
+    let mut count = 0;
+    for i in 0..stacks.len() {
+        let near = edge[i]
+            .iter()
+            .filter(|&&j| j < i)
+            .map(|&j| color[j])
+            .collect::<HashSet<_>>();
+        color[i] = (0..).find(|x| !near.contains(x)).unwrap();
+        count = count.max(color[i] + 1);
+    }
+
+    let size = 4 + count;
+    let ids = stacks
+        .iter()
+        .zip(&color)
+        .map(|(x, c)| (*x, 4 + c))
+        .collect::<HashMap<_, _>>();
+    for (c, d) in code.iter().zip(dots.iter_mut()) {
+        if c.get_type() != 0 && *d > 3 {
+            *d = ids.get(d).copied().unwrap_or(size);
+        }
+    }
+
+    Allocation { dots, ids, size }
+}
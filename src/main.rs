@@ -1,10 +1,40 @@
 use clap::*;
 use hyeong::state::{State, UnOptState};
-use hyeong::{build, debug, execute, interpreter, io, optimize};
+use hyeong::{build, dap, debug, execute, interpreter, io, optimize};
 use std::io::{stderr, stdin, stdout, Write};
 use std::path::Path;
 use std::process::Command;
 
+/// Optimization config of `-O` level changed by `-C` and `--emit` options
+/// It returns `None` if there is no such option.
+#[cfg_attr(tarpaulin, skip)]
+fn codegen(matches: &ArgMatches, level: usize) -> Option<optimize::Config> {
+    if !matches.is_present("codegen") && !matches.is_present("emit") {
+        return None;
+    }
+    let mut config = optimize::Config::new(level);
+    for opt in matches.values_of("codegen").into_iter().flatten() {
+        if let Err(e) = config.set(opt) {
+            io::print_error_string(&e);
+        }
+    }
+    config.report = matches.value_of("emit") == Some("opt-report");
+    if config.passes.is_empty() {
+        io::print_log("optimizing without passes");
+    } else {
+        io::print_log(&*format!(
+            "optimizing with passes {}",
+            config
+                .passes
+                .iter()
+                .map(|x| x.name())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    Some(config)
+}
+
 /// Main function of this program
 ///
 /// ```text
@@ -24,6 +54,7 @@ use std::process::Command;
 ///     debug        Debug your code command by command
 ///     help         Prints this message or the help of the given subcommand(s)
 ///     install      Install hyeong before build (need once)
+///     repl         Run interactive interpreter
 ///     run          Run hyeong code directly
 ///     uninstall    Uninstall hyeong before build
 /// ```
@@ -48,9 +79,27 @@ fn main() {
                         .takes_value(true)
                         .short("O")
                         .long("optimize")
-                        .help("optimize level (0: no optimize, 1: basic optimize, 2: hard optimize")
+                        .help("optimize level (0: no optimize, 1: basic optimize, 2: hard optimize, 3: stack liveness")
                         .default_value("2"),
                 )
+                .arg(
+                    Arg::with_name("codegen")
+                        .value_name("OPTION")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .short("C")
+                        .long("codegen")
+                        .help("optimizer option (passes=PASS,... to choose passes in order, dump-passes to print code after each pass)"),
+                )
+                .arg(
+                    Arg::with_name("emit")
+                        .value_name("KIND")
+                        .takes_value(true)
+                        .possible_values(&["opt-report"])
+                        .long("emit")
+                        .help("print extra output (opt-report: what the optimizer did)"),
+                )
                 .arg(
                     Arg::with_name("output")
                         .value_name("output")
@@ -78,7 +127,7 @@ fn main() {
                     Arg::with_name("input")
                         .value_name("input_file")
                         .takes_value(true)
-                        .required(true)
+                        .required_unless("dap")
                         .help("input file to debug"),
                 )
                 .arg(
@@ -87,8 +136,74 @@ fn main() {
                         .takes_value(true)
                         .short("f")
                         .long("from")
-                        .help("place to start debugging from")
+                        .help("place to start debugging from (LINE, LINE:COL, FILE:LINE or #NUM)")
+                        .default_value("#0"),
+                )
+                .arg(
+                    Arg::with_name("optimize")
+                        .value_name("optimize")
+                        .takes_value(true)
+                        .short("O")
+                        .long("optimize")
+                        .help("optimize level to debug (0: no optimize, 1: basic optimize, 2: hard optimize, 3: stack liveness)")
                         .default_value("0"),
+                )
+                .arg(
+                    Arg::with_name("program_input")
+                        .value_name("program_input")
+                        .takes_value(true)
+                        .short("i")
+                        .long("input")
+                        .help("file to use as input of the program"),
+                )
+                .arg(
+                    Arg::with_name("script")
+                        .value_name("script")
+                        .takes_value(true)
+                        .short("s")
+                        .long("script")
+                        .help("file to read debugger commands from"),
+                )
+                .arg(
+                    Arg::with_name("batch")
+                        .short("b")
+                        .long("batch")
+                        .help("turn off prompts and colors for scripts"),
+                )
+                .arg(
+                    Arg::with_name("screen")
+                        .long("screen")
+                        .conflicts_with_all(&["batch", "script"])
+                        .help("show full-screen view with commands bound to keys"),
+                )
+                .arg(
+                    Arg::with_name("dap")
+                        .long("dap")
+                        .help("run as debug adapter protocol server on stdin and stdout"),
+                ),
+        )
+        .subcommand(
+            App::new("repl")
+                .about("Run interactive interpreter")
+                .arg(
+                    Arg::with_name("session")
+                        .value_name("session")
+                        .takes_value(true)
+                        .long("session")
+                        .help("file to restore state from and save state to on exit"),
+                )
+                .arg(
+                    Arg::with_name("program_input")
+                        .value_name("program_input")
+                        .takes_value(true)
+                        .short("i")
+                        .long("input")
+                        .help("file to use as input of the program"),
+                )
+                .arg(
+                    Arg::with_name("raw")
+                        .long("raw")
+                        .help("no banner, prompts and decorations (default when not on a terminal)"),
                 ),
         )
         .subcommand(
@@ -108,9 +223,33 @@ fn main() {
                         .short("O")
                         .long("optimize")
                         .help(
-                            "optimize level (0: no optimize, 1: basic optimize, 2: hard optimize)",
+                            "optimize level (0: no optimize, 1: basic optimize, 2: hard optimize, 3: stack liveness)",
                         )
                         .default_value("2"),
+                )
+                .arg(
+                    Arg::with_name("codegen")
+                        .value_name("OPTION")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .short("C")
+                        .long("codegen")
+                        .help("optimizer option (passes=PASS,... to choose passes in order, dump-passes to print code after each pass)"),
+                )
+                .arg(
+                    Arg::with_name("emit")
+                        .value_name("KIND")
+                        .takes_value(true)
+                        .possible_values(&["opt-report"])
+                        .long("emit")
+                        .help("print extra output (opt-report: what the optimizer did)"),
+                )
+                .arg(
+                    Arg::with_name("interactive")
+                        .short("i")
+                        .long("interactive")
+                        .help("start interpreter with the final state (runs without optimize)"),
                 ),
         )
         .subcommand(App::new("install").about("Install hyeong before build (need once)"))
@@ -130,7 +269,17 @@ fn main() {
             }
         };
 
-        let source = if level >= 1 {
+        let source = if let Some(config) = codegen(matches, level) {
+            // code should start from the state left by prefix-eval, as it does from level 2
+            let level = if config.passes.iter().any(|x| x.name() == "prefix-eval") {
+                level.max(2)
+            } else {
+                level
+            };
+            let (state, opt_code) = optimize::optimize_with(un_opt_code, &config);
+            io::print_log("compiling to rust");
+            build::build_source(state, &opt_code, level)
+        } else if level >= 1 {
             let (state, opt_code) = optimize::optimize(un_opt_code, level);
             io::print_log("compiling to rust");
             build::build_source(state, &opt_code, level)
@@ -189,10 +338,49 @@ fn main() {
             println!("{}:{}", file, c.to_string())
         }
     } else if let Some(ref matches) = matches.subcommand_matches("debug") {
+        if matches.is_present("dap") {
+            dap::run();
+        }
+        let mode = if matches.is_present("batch") {
+            colored::control::set_override(false);
+            debug::Mode::Batch
+        } else if matches.is_present("screen") {
+            debug::Mode::Screen
+        } else {
+            debug::Mode::Line
+        };
         let file = matches.value_of("input").unwrap();
         let code = io::read_file(file);
-        let from = io::handle_error(matches.value_of("from").unwrap().parse::<usize>());
-        debug::run(code, from);
+        let from = if code.is_empty() {
+            0
+        } else {
+            match debug::find_location(&code, file, matches.value_of("from").unwrap()) {
+                Ok(t) => t,
+                Err(e) => io::print_error_string(&e),
+            }
+        };
+        let mut ipt = io::QueueReader::new();
+        if let Some(t) = matches.value_of("program_input") {
+            ipt.push(&io::read_text(t));
+        }
+        let level = io::handle_error(matches.value_of("optimize").unwrap().parse::<usize>());
+        let opt = if level >= 1 {
+            Some(optimize::optimize(code.clone(), level))
+        } else {
+            None
+        };
+        let script = matches.value_of("script").map(|t| {
+            let mut cmd = io::QueueReader::new();
+            cmd.push(&io::read_text(t));
+            cmd
+        });
+
+        match (script, opt) {
+            (Some(cmd), Some(opt)) => debug::run_opt(file, code, opt, from, ipt, cmd, mode),
+            (Some(cmd), None) => debug::run(file, code, from, ipt, cmd, mode),
+            (None, Some(opt)) => debug::run_opt(file, code, opt, from, ipt, stdin(), mode),
+            (None, None) => debug::run(file, code, from, ipt, stdin(), mode),
+        }
     } else if let Some(ref matches) = matches.subcommand_matches("run") {
         let file = matches.value_of("input").unwrap();
         let un_opt_code = io::read_file(file);
@@ -202,16 +390,31 @@ fn main() {
         let mut stdout = stdout();
         let mut stderr = stderr();
 
-        if level >= 1 {
-            let (mut state, opt_code) = optimize::optimize(un_opt_code, level);
+        if matches.is_present("interactive") {
+            let mut state = UnOptState::new();
+            io::print_log("running code");
+            for code in un_opt_code {
+                let (t, exit) =
+                    execute::execute_no_exit(&mut stdin(), &mut stdout, &mut stderr, state, &code);
+                state = t;
+                if let Some(t) = exit {
+                    io::print_log(&*format!("program exited with code {}", t));
+                    break;
+                }
+            }
+            io::handle_error(stdout.flush());
+            io::handle_error(stderr.flush());
+            interpreter::run(state, None, io::QueueReader::new(), false);
+        } else if level >= 1 || matches.is_present("codegen") || matches.is_present("emit") {
+            let (mut state, opt_code) = match codegen(matches, level) {
+                Some(config) => optimize::optimize_with(un_opt_code, &config),
+                None => optimize::optimize(un_opt_code, level),
+            };
             io::print_log("running code");
 
             if !state.get_stack(1).is_empty() {
                 for num in state.get_stack(1).iter() {
-                    io::write(
-                        &mut stdout,
-                        &*format!("{}", num.floor().to_int() as u8 as char),
-                    );
+                    io::write(&mut stdout, &*format!("{}", optimize::output_char(num)));
                 }
                 io::handle_error(stdout.flush());
                 state.get_stack(1).clear();
@@ -219,10 +422,7 @@ fn main() {
 
             if !state.get_stack(2).is_empty() {
                 for num in state.get_stack(2).iter() {
-                    io::write(
-                        &mut stderr,
-                        &*format!("{}", num.floor().to_int() as u8 as char),
-                    );
+                    io::write(&mut stderr, &*format!("{}", optimize::output_char(num)));
                 }
                 io::handle_error(stderr.flush());
                 state.get_stack(2).clear();
@@ -266,7 +466,14 @@ fn main() {
         io::print_log("uninstalling hyeong");
         io::execute_command_stdout("rmdir /S %USERPROFILE%\\.hyeong", "rm -rf ~/.hyeong");
         io::print_log("done!");
+    } else if let Some(ref matches) = matches.subcommand_matches("repl") {
+        let mut ipt = io::QueueReader::new();
+        if let Some(t) = matches.value_of("program_input") {
+            ipt.push(&io::read_text(t));
+        }
+        let raw = matches.is_present("raw");
+        interpreter::run(UnOptState::new(), matches.value_of("session"), ipt, raw);
     } else {
-        interpreter::run();
+        interpreter::run(UnOptState::new(), None, io::QueueReader::new(), false);
     }
 }
@@ -456,12 +456,18 @@ impl PartialOrd for Num {
         if self.is_nan() || other.is_nan() {
             Option::None
         } else {
-            if self == other {
-                Option::Some(Ordering::Equal)
-            } else if &self.up * &other.down < &self.down * &other.down {
-                Option::Some(Ordering::Less)
+            // a/b < c/d is a*d < c*b if b*d is positive, and a*d > c*b if not
+            let lhs = &self.up * &other.down;
+            let rhs = &other.up * &self.down;
+            let ord = if lhs.is_zero() && rhs.is_zero() {
+                Ordering::Equal
             } else {
-                Option::Some(Ordering::Greater)
+                lhs.partial_cmp(&rhs)?
+            };
+            if self.down.is_pos() == other.down.is_pos() {
+                Option::Some(ord)
+            } else {
+                Option::Some(ord.reverse())
             }
         }
     }
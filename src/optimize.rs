@@ -1,30 +1,70 @@
 use crate::code::{Code, OptCode, UnOptCode};
 use crate::execute::{pop_stack_wrap, push_stack_wrap};
-use crate::io::ReadLine;
 use crate::number::Num;
 use crate::state::{OptState, State};
-use crate::{area, io};
+use crate::{area, io, liveness, parse, peephole};
 use std::collections::HashMap;
-use std::io::{stdin, Write};
+use std::fmt;
+use std::io::Write;
 
-/// Optimization helper function for level 2 optimization
+/// Reason why `prefix-eval` pass stopped
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    /// Code reads input from stack 0
+    Input,
+    /// Code pops stack 1 or 2, which exits the program
+    Exit,
+    /// Code jumps 100 times in one command
+    Loop,
+}
+
+impl Stop {
+    /// Reason of popping stack `idx` that is not allowed
+    fn pop(idx: usize) -> Stop {
+        if idx == 0 {
+            Stop::Input
+        } else {
+            Stop::Exit
+        }
+    }
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Stop::Input => "input read",
+                Stop::Exit => "pop of stack 1 or 2",
+                Stop::Loop => "100 jumps",
+            }
+        )
+    }
+}
+
+/// Optimization helper function for `prefix-eval` pass
+///
+/// It returns `Some` reason with the state before `code` if it can not run `code`.
+/// It stops before popping stack 0, so it never reads input.
 fn opt_execute<T>(
-    ipt: &mut impl ReadLine,
     out: &mut impl Write,
     err: &mut impl Write,
     mut state: T,
     code: &T::CodeType,
-) -> (T, bool)
+) -> (T, Option<Stop>)
 where
     T: State + Clone,
 {
+    // input is not read, but `pop_stack_wrap` needs reader without side effect
+    let ipt = &mut io::QueueReader::new();
     let state_clone = state.clone();
     let mut cur_loc = state.push_code((*code).clone());
     let length = cur_loc + 1;
     let mut exec_count = 0;
     while cur_loc < length {
         if exec_count >= 100 {
-            return (state_clone, false);
+            return (state_clone, Some(Stop::Loop));
         }
 
         let code = (*state.get_code(cur_loc)).clone();
@@ -45,7 +85,7 @@ where
                 let mut n = Num::zero();
                 for _ in 0..code.get_hangul_count() {
                     if cur_stack <= 2 {
-                        return (state_clone, false);
+                        return (state_clone, Some(Stop::pop(cur_stack)));
                     }
                     n += &pop_stack_wrap(ipt, out, err, &mut state, cur_stack);
                 }
@@ -55,7 +95,7 @@ where
                 let mut n = Num::one();
                 for _ in 0..code.get_hangul_count() {
                     if cur_stack <= 2 {
-                        return (state_clone, false);
+                        return (state_clone, Some(Stop::pop(cur_stack)));
                     }
                     n *= &pop_stack_wrap(ipt, out, err, &mut state, cur_stack);
                 }
@@ -67,7 +107,7 @@ where
 
                 for _ in 0..code.get_hangul_count() {
                     if cur_stack <= 2 {
-                        return (state_clone, false);
+                        return (state_clone, Some(Stop::pop(cur_stack)));
                     }
                     v.push(pop_stack_wrap(ipt, out, err, &mut state, cur_stack));
                 }
@@ -86,7 +126,7 @@ where
 
                 for _ in 0..code.get_hangul_count() {
                     if cur_stack <= 2 {
-                        return (state_clone, false);
+                        return (state_clone, Some(Stop::pop(cur_stack)));
                     }
                     v.push(pop_stack_wrap(ipt, out, err, &mut state, cur_stack));
                 }
@@ -102,7 +142,7 @@ where
             // 5
             _ => {
                 if cur_stack <= 2 {
-                    return (state_clone, false);
+                    return (state_clone, Some(Stop::pop(cur_stack)));
                 }
                 let n = pop_stack_wrap(ipt, out, err, &mut state, cur_stack);
                 for _ in 0..code.get_hangul_count() {
@@ -122,7 +162,7 @@ where
             }
         }) {
             Some(value) => value,
-            None => return (state_clone, false),
+            None => return (state_clone, Some(Stop::pop(cur_stack))),
         };
 
         if area_type != 0 {
@@ -151,134 +191,616 @@ where
         cur_loc += 1;
     }
 
-    (state, true)
+    (state, None)
 }
 
-/// Optimization function
+/// Stack numbers for `renumber` pass
 ///
-/// ## Level 1
-///
-/// In level 1, it analyzes the hyeong code and collect only used index of stack.
-/// Then, re-number the indices not to make `HashMap`; using `Vec`
-///
-/// ## Level 2
-///
-/// In level 2, it runs code until it gets input, terminates, or too much loop.
-/// So, `Hello, World!` code compiles to `print!("Hello, World!")` when unused code is removed.
+/// It returns map from dot count of stack to new index and the largest index.
+/// Stacks 0 to 3 are not re-numbered.
+/// Stacks that are never the current stack are never popped,
+/// so they share one index.
 ///
 /// # Examples
 ///
 /// ```
 /// use hyeong::{optimize, parse};
-/// use hyeong::state::State;
 ///
-/// let a = parse::parse("형... 항.".to_string());
-/// let (mut s, c) = optimize::optimize(a, 2);
+/// let (m, max) = optimize::stack_map(&parse::parse("흑..... 항...... 항.......".to_string()));
 ///
-/// assert_eq!("3", s.get_stack(1).iter().map(|x| x.to_string()).collect::<Vec<_>>().join(""))
+/// assert_eq!(4, m[&5]);
+/// assert_eq!(5, m[&6]);
+/// assert_eq!(5, m[&7]);
+/// assert_eq!(5, max);
 /// ```
-pub fn optimize(code: Vec<UnOptCode>, level: usize) -> (OptState, Vec<OptCode>) {
-    let mut size = 0usize;
-    let mut opt_code_vec: Vec<OptCode> = Vec::new();
+pub fn stack_map<T: Code>(code: &[T]) -> (HashMap<usize, usize>, usize) {
+    let mut dot_map: HashMap<usize, usize> = HashMap::new();
+    let mut max: usize = 4;
+    let mut now = 3;
+    let mut chk = Vec::new();
 
-    if level >= 3 {
-        io::print_error_string(&*format!("optimize level {} is not supported", level));
+    for un_opt_code in code {
+        if un_opt_code.get_type() == 0 {
+            continue;
+        }
+        chk.push(now);
+        if un_opt_code.get_type() == 5 {
+            now = un_opt_code.get_dot_count();
+        }
     }
 
-    io::print_log(&*format!("optimizing to level {}", level));
-
-    if level >= 1 {
-        let mut dot_map: HashMap<usize, usize> = HashMap::new();
-        let mut max: usize = 4;
-        let mut now = 3;
-        let mut chk = Vec::new();
+    chk.sort();
+    for i in chk {
+        if i <= 3 {
+            continue;
+        }
+        let temp = dot_map.entry(i).or_insert(0);
+        if *temp == 0 {
+            *temp = max;
+            max += 1;
+        }
+    }
 
-        for un_opt_code in &code {
-            if un_opt_code.get_type() == 0 {
-                continue;
-            }
-            chk.push(now);
-            if un_opt_code.get_type() == 5 {
-                now = un_opt_code.get_dot_count();
-            }
+    for un_opt_code in code {
+        if un_opt_code.get_type() != 0 && un_opt_code.get_dot_count() > 3 {
+            dot_map.entry(un_opt_code.get_dot_count()).or_insert(max);
         }
+    }
+
+    (dot_map, max)
+}
+
+/// Optimization pass
+///
+/// It gets code left to run and the state made by previous passes,
+/// and returns new code and state that run the same.
+/// Code already run is kept in the state.
+pub trait Pass {
+    /// Name of pass used in `-C passes=`
+    fn name(&self) -> &'static str;
+
+    /// Run pass, and write what it did to `report`
+    fn run(
+        &self,
+        code: Vec<OptCode>,
+        state: OptState,
+        report: &mut Report,
+    ) -> (Vec<OptCode>, OptState);
+}
+
+/// Optimization report of `--emit opt-report`
+///
+/// Passes write what they did, and the state after all passes is saved at the end.
+#[derive(Default)]
+pub struct Report {
+    /// Map from dot count to new stack index of `renumber` pass
+    pub stack_map: Option<Vec<(usize, usize)>>,
+    /// Number of commands `prefix-eval` pass ran, and why it stopped
+    pub prefix_eval: Option<(usize, usize, Option<Stop>)>,
+    /// Non-empty stacks of the initial state
+    pub stacks: Vec<(usize, Vec<Num>)>,
+    /// Heart points of the initial state
+    pub points: Vec<(u128, usize)>,
+    /// Current stack of the initial state
+    pub current_stack: usize,
+}
 
-        chk.sort();
-        for i in chk {
-            if i <= 3 {
-                continue;
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(map) = &self.stack_map {
+            writeln!(
+                f,
+                "renumber:{}",
+                if map.is_empty() { " no stacks" } else { "" }
+            )?;
+            for (a, b) in map {
+                writeln!(f, "  stack {} -> {}", a, b)?;
             }
-            let temp = dot_map.entry(i).or_insert(0);
-            if *temp == 0 {
-                *temp = max;
-                max += 1;
+        }
+        if let Some((ran, total, stop)) = &self.prefix_eval {
+            write!(f, "prefix-eval: ran {} of {} commands", ran, total)?;
+            match stop {
+                Some(t) => writeln!(f, ", stopped by {}", t)?,
+                None => writeln!(f)?,
             }
         }
+        writeln!(f, "initial current stack: {}", self.current_stack)?;
+        for (i, v) in &self.stacks {
+            writeln!(
+                f,
+                "initial stack {}: [{}]",
+                i,
+                v.iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        for (id, loc) in &self.points {
+            writeln!(f, "heart point {}: {}", area::point_id_string(*id), loc)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pass that re-numbers stacks with [stack_map](fn.stack_map.html)
+///
+/// It makes stack of `OptState` small, and changes stacks and code in the state too.
+pub struct Renumber;
 
-        for un_opt_code in &code {
-            let opt_type_ = un_opt_code.get_type();
-            let opt_hangul_count = un_opt_code.get_hangul_count();
-            let mut opt_dot_count = un_opt_code.get_dot_count();
-            let opt_area_count = un_opt_code.get_area_count();
-            let opt_area = un_opt_code.get_area().clone();
-
-            if opt_type_ == 0 || un_opt_code.get_dot_count() <= 3 {
-                opt_code_vec.push(OptCode::new(
-                    opt_type_,
-                    opt_hangul_count,
-                    opt_dot_count,
-                    opt_area_count,
-                    opt_area,
-                ));
+impl Pass for Renumber {
+    fn name(&self) -> &'static str {
+        "renumber"
+    }
+
+    fn run(
+        &self,
+        code: Vec<OptCode>,
+        mut state: OptState,
+        report: &mut Report,
+    ) -> (Vec<OptCode>, OptState) {
+        let mut done = state.get_all_code();
+        let start = done.len();
+        done.extend(code);
+        let (dot_map, max) = stack_map(&done);
+        let mut map = dot_map.iter().map(|(&a, &b)| (a, b)).collect::<Vec<_>>();
+        map.sort_unstable();
+        report.stack_map = Some(map);
+        let id = |x: usize| {
+            if x <= 3 {
+                x
             } else {
-                let temp = dot_map.entry(opt_dot_count).or_insert(0);
-                if *temp == 0 {
-                    *temp = max;
+                dot_map.get(&x).copied().unwrap_or(max)
+            }
+        };
+
+        let dots = done
+            .iter()
+            .map(|c| {
+                if c.get_type() == 0 {
+                    c.get_dot_count()
+                } else {
+                    id(c.get_dot_count())
                 }
-                opt_dot_count = *temp;
-                opt_code_vec.push(OptCode::new(
-                    opt_type_,
-                    opt_hangul_count,
-                    opt_dot_count,
-                    opt_area_count,
-                    opt_area,
-                ));
+            })
+            .collect::<Vec<_>>();
+        remap(&done, start, &dots, &mut state, max + 1, |x| Some(id(x)))
+    }
+}
+
+/// Pass that folds short sequences of commands with [peephole](../peephole/fn.fold.html)
+///
+/// Sequences that can be reached by a heart jump from elsewhere are not changed.
+pub struct Peephole;
+
+impl Pass for Peephole {
+    fn name(&self) -> &'static str {
+        "peephole"
+    }
+
+    fn run(&self, code: Vec<OptCode>, state: OptState, _: &mut Report) -> (Vec<OptCode>, OptState) {
+        let mut done = state.get_all_code();
+        let start = done.len();
+        done.extend(code);
+        let live = liveness::analyze(
+            &done,
+            start,
+            state.current_stack(),
+            &state.get_all_point(),
+            state.get_latest_loc(),
+        );
+
+        let res = peephole::fold(&done, start, &live, state.stack_size());
+        (res[start..].to_vec(), state)
+    }
+}
+
+/// Pass that removes pushes to stacks never read and merges stacks with [liveness](../liveness/index.html)
+///
+/// Stacks that can not be non-empty at the same time share one index.
+pub struct StackLiveness;
+
+impl Pass for StackLiveness {
+    fn name(&self) -> &'static str {
+        "liveness"
+    }
+
+    fn run(
+        &self,
+        code: Vec<OptCode>,
+        mut state: OptState,
+        _: &mut Report,
+    ) -> (Vec<OptCode>, OptState) {
+        let mut done = state.get_all_code();
+        let start = done.len();
+        done.extend(code);
+        let heights = state
+            .get_all_stack_index()
+            .into_iter()
+            .filter(|&i| i > 3)
+            .map(|i| (i, state.get_stack(i).len()))
+            .collect::<HashMap<_, _>>();
+        let res = liveness::allocate(
+            &done,
+            start,
+            state.current_stack(),
+            &state.get_all_point(),
+            state.get_latest_loc(),
+            &heights,
+        );
+
+        remap(&done, start, &res.dots, &mut state, res.size, |x| {
+            if x <= 3 {
+                Some(x)
+            } else {
+                res.ids.get(&x).copied()
             }
-        }
+        })
+    }
+}
 
-        size = max + 1;
+/// Make code and state with new stack numbers
+///
+/// `code` is all code including `code[..start]` that is in `state`,
+/// and `dots` is new dot count of each command.
+/// Values of stack `i` move to stack `id(i)`, and they are removed if it is `None`.
+fn remap<F>(
+    code: &[OptCode],
+    start: usize,
+    dots: &[usize],
+    state: &mut OptState,
+    size: usize,
+    id: F,
+) -> (Vec<OptCode>, OptState)
+where
+    F: Fn(usize) -> Option<usize>,
+{
+    let mut res = code
+        .iter()
+        .zip(dots)
+        .map(|(c, &dot)| {
+            let mut opt_code = OptCode::new(
+                c.get_type(),
+                c.get_hangul_count(),
+                dot,
+                c.get_area_count(),
+                c.get_area().clone(),
+            );
+            if let Some(t) = c.get_source() {
+                opt_code.set_source(t);
+            }
+            opt_code
+        })
+        .collect::<Vec<_>>();
+
+    let mut new_state = OptState::new(size);
+    for i in state.get_all_stack_index() {
+        let v = state.get_stack(i).drain(..).collect::<Vec<_>>();
+        if let Some(t) = id(i) {
+            new_state.get_stack(t).extend(v);
+        }
     }
+    for (a, b) in state.get_all_point() {
+        new_state.set_point(a, b);
+    }
+    if let Some(t) = state.get_latest_loc() {
+        new_state.set_latest_loc(t);
+    }
+    new_state.set_current_stack(id(state.current_stack()).unwrap_or(size));
+    let code = res.split_off(start);
+    new_state.swap_code(&mut res);
 
-    let mut state = OptState::new(size);
+    (code, new_state)
+}
+
+/// Pass that runs code until it gets input, terminates, or too much loop
+///
+/// So, `Hello, World!` code compiles to `print!("Hello, World!")` when unused code is removed.
+/// Output is saved to stack 1 and 2 of the state.
+pub struct PrefixEval;
 
-    if level >= 2 {
-        let mut out = io::CustomWriter::new(|_| Result::Ok(()));
-        let mut err = io::CustomWriter::new(|_| Result::Ok(()));
+impl Pass for PrefixEval {
+    fn name(&self) -> &'static str {
+        "prefix-eval"
+    }
 
-        let mut idx = opt_code_vec.len();
-        for (i, opt_code) in opt_code_vec.iter().enumerate() {
-            let (new_state, next) = opt_execute(&mut stdin(), &mut out, &mut err, state, opt_code);
+    fn run(
+        &self,
+        code: Vec<OptCode>,
+        mut state: OptState,
+        report: &mut Report,
+    ) -> (Vec<OptCode>, OptState) {
+        let mut idx = code.len();
+        let mut reason = None;
+        for (i, opt_code) in code.iter().enumerate() {
+            // output of a command that can not run is thrown away with its state
+            let mut out = io::CustomWriter::new(|_| Result::Ok(()));
+            let mut err = io::CustomWriter::new(|_| Result::Ok(()));
+            let (new_state, stop) = opt_execute(&mut out, &mut err, state, opt_code);
             state = new_state;
-            if !next {
+            if stop.is_some() {
                 idx = i;
+                reason = stop;
                 break;
             }
+
+            state
+                .get_stack(1)
+                .extend(out.to_string().chars().map(|x| Num::from_num(x as isize)));
+            state
+                .get_stack(2)
+                .extend(err.to_string().chars().map(|x| Num::from_num(x as isize)));
         }
-        opt_code_vec = opt_code_vec[idx..].to_vec();
+        report.prefix_eval = Some((idx, code.len(), reason));
 
-        state.get_stack(1).extend(
-            out.to_string()
-                .as_bytes()
-                .iter()
-                .map(|&x| Num::from_num(x as isize)),
-        );
-        state.get_stack(2).extend(
-            err.to_string()
-                .as_bytes()
-                .iter()
-                .map(|&x| Num::from_num(x as isize)),
+        (code[idx..].to_vec(), state)
+    }
+}
+
+/// Character of a value in stack 1 or 2 of the state, that is output left by `prefix-eval` pass
+///
+/// Output is saved as characters, not bytes, since it can have any character like `NaN` message.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::number::Num;
+/// use hyeong::optimize;
+///
+/// assert_eq!('A', optimize::output_char(&Num::from_num(65)));
+/// assert_eq!('형', optimize::output_char(&Num::from_num('형' as isize)));
+/// ```
+pub fn output_char(num: &Num) -> char {
+    std::char::from_u32(num.floor().to_int()).unwrap_or(std::char::REPLACEMENT_CHARACTER)
+}
+
+/// Return pass of `name`
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::optimize;
+///
+/// assert_eq!("renumber", optimize::get_pass("renumber").unwrap().name());
+/// assert!(optimize::get_pass("foo").is_none());
+/// ```
+pub fn get_pass(name: &str) -> Option<Box<dyn Pass>> {
+    match name {
+        "renumber" => Some(Box::new(Renumber)),
+        "prefix-eval" => Some(Box::new(PrefixEval)),
+        "peephole" => Some(Box::new(Peephole)),
+        "liveness" => Some(Box::new(StackLiveness)),
+        _ => None,
+    }
+}
+
+/// Options of optimization
+///
+/// It is made from optimize level, and changed by `-C` options.
+pub struct Config {
+    /// Passes to run in order
+    pub passes: Vec<Box<dyn Pass>>,
+    /// Print code after each pass
+    pub dump: bool,
+    /// Print [Report](struct.Report.html) after all passes
+    pub report: bool,
+}
+
+impl Config {
+    /// Make config of optimize `level`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::optimize::Config;
+    ///
+    /// let a = Config::new(2);
+    /// assert_eq!(vec!["renumber", "prefix-eval"], a.passes.iter().map(|x| x.name()).collect::<Vec<_>>());
+    /// ```
+    pub fn new(level: usize) -> Config {
+        if level >= 4 {
+            io::print_error_string(&*format!("optimize level {} is not supported", level));
+        }
+
+        let names: &[&str] = match level {
+            0 => &[],
+            1 => &["renumber"],
+            2 => &["renumber", "prefix-eval"],
+            _ => &["renumber", "prefix-eval", "peephole", "liveness"],
+        };
+        Config {
+            passes: names.iter().map(|x| get_pass(x).unwrap()).collect(),
+            dump: false,
+            report: false,
+        }
+    }
+
+    /// Set option of `-C`
+    ///
+    /// - `passes=PASS,PASS,...`: run only the passes in the order
+    /// - `dump-passes`: print code after each pass
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::optimize::Config;
+    ///
+    /// let mut a = Config::new(2);
+    /// a.set("passes=prefix-eval").unwrap();
+    /// a.set("dump-passes").unwrap();
+    ///
+    /// assert_eq!(vec!["prefix-eval"], a.passes.iter().map(|x| x.name()).collect::<Vec<_>>());
+    /// assert!(a.dump);
+    /// assert!(a.set("passes=foo").is_err());
+    /// assert!(a.set("foo").is_err());
+    /// ```
+    pub fn set(&mut self, opt: &str) -> Result<(), String> {
+        match opt.find('=') {
+            Some(i) if &opt[..i] == "passes" => {
+                self.passes = opt[i + 1..]
+                    .split(',')
+                    .filter(|x| !x.is_empty())
+                    .map(|x| get_pass(x).ok_or_else(|| format!("unknown pass \"{}\"", x)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(())
+            }
+            None if opt == "dump-passes" => {
+                self.dump = true;
+                Ok(())
+            }
+            _ => Err(format!("unknown option \"{}\"", opt)),
+        }
+    }
+}
+
+/// Print code and state after pass `name`
+#[cfg_attr(tarpaulin, skip)]
+fn dump(name: &str, code: &[OptCode], state: &mut OptState) {
+    io::print_log(&*format!("code after {}", name));
+    let done = state.get_all_code().len();
+    for (i, c) in state.get_all_code().iter().chain(code.iter()).enumerate() {
+        println!(
+            "{}{} {}_{}_{} : {}",
+            if i < done { "done " } else { "" },
+            i,
+            parse::COMMANDS[c.get_type() as usize],
+            c.get_hangul_count(),
+            c.get_dot_count(),
+            c.get_area()
         );
     }
+    println!("current stack: {}", state.current_stack());
+    for i in state.get_all_stack_index() {
+        if !state.get_stack(i).is_empty() {
+            println!("stack {}: {:?}", i, state.get_stack(i));
+        }
+    }
+}
 
-    (state, opt_code_vec)
+/// Optimization function with `config`
+///
+/// It makes `OptCode` with the same stack numbers, then runs passes of `config` in order.
+/// Stacks of the state are as many as the largest stack number, until `renumber` pass runs.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{optimize, parse};
+/// use hyeong::optimize::Config;
+/// use hyeong::state::State;
+///
+/// let mut config = Config::new(0);
+/// config.set("passes=prefix-eval").unwrap();
+/// let (mut s, c) = optimize::optimize_with(parse::parse("형... 흑.... 항.".to_string()), &config);
+///
+/// assert!(c.is_empty());
+/// assert_eq!(5, s.stack_size());
+/// assert_eq!("3", s.get_stack(1).iter().map(|x| x.to_string()).collect::<Vec<_>>().join(""))
+/// ```
+pub fn optimize_with(code: Vec<UnOptCode>, config: &Config) -> (OptState, Vec<OptCode>) {
+    let (state, code, report) = optimize_report(code, config);
+    if config.report {
+        io::print_log("optimization report");
+        print!("{}", report);
+    }
+    (state, code)
+}
+
+/// Optimization function with `config` that also returns [Report](struct.Report.html)
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{optimize, parse};
+/// use hyeong::optimize::{Config, Stop};
+///
+/// let (_, c, r) = optimize::optimize_report(parse::parse("형... 흑.... 항. 항..... 흑 항".to_string()), &Config::new(2));
+///
+/// assert_eq!(1, c.len());
+/// assert_eq!(Some(vec![(4, 4), (5, 5)]), r.stack_map);
+/// assert_eq!(Some((5, 6, Some(Stop::Input))), r.prefix_eval);
+/// ```
+pub fn optimize_report(code: Vec<UnOptCode>, config: &Config) -> (OptState, Vec<OptCode>, Report) {
+    let size = code
+        .iter()
+        .filter(|x| x.get_type() != 0)
+        .map(|x| x.get_dot_count())
+        .max()
+        .unwrap_or(0)
+        .max(3)
+        + 1;
+    let mut state = OptState::new(size);
+    let mut opt_code_vec = code
+        .iter()
+        .enumerate()
+        .map(|(i, un_opt_code)| {
+            let mut opt_code = OptCode::new(
+                un_opt_code.get_type(),
+                un_opt_code.get_hangul_count(),
+                un_opt_code.get_dot_count(),
+                un_opt_code.get_area_count(),
+                un_opt_code.get_area().clone(),
+            );
+            opt_code.set_source(i);
+            opt_code
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = Report::default();
+    for pass in &config.passes {
+        let (c, s) = pass.run(opt_code_vec, state, &mut report);
+        opt_code_vec = c;
+        state = s;
+        if config.dump {
+            dump(pass.name(), &opt_code_vec, &mut state);
+        }
+    }
+
+    report.current_stack = state.current_stack();
+    for i in state.get_all_stack_index() {
+        if !state.get_stack(i).is_empty() {
+            report.stacks.push((i, state.get_stack(i).clone()));
+        }
+    }
+    report.points = state.get_all_point();
+    report.points.sort_unstable();
+
+    (state, opt_code_vec, report)
+}
+
+/// Optimization function
+///
+/// ## Level 1
+///
+/// In level 1, it analyzes the hyeong code and collect only used index of stack.
+/// Then, re-number the indices not to make `HashMap`; using `Vec`
+///
+/// ## Level 2
+///
+/// In level 2, it runs code until it gets input, terminates, or too much loop.
+/// So, `Hello, World!` code compiles to `print!("Hello, World!")` when unused code is removed.
+///
+/// ## Level 3
+///
+/// In level 3, it finds stacks that can be read after each command.
+/// Pushes to stacks never read are removed, and stacks that are not used at the same time are merged.
+/// Before that, short sequences of constant pushes and double negations are folded.
+///
+/// Each level is a list of passes in [Config](struct.Config.html).
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{optimize, parse};
+/// use hyeong::state::State;
+///
+/// let a = parse::parse("형... 항.".to_string());
+/// let (mut s, c) = optimize::optimize(a, 2);
+///
+/// assert_eq!("3", s.get_stack(1).iter().map(|x| x.to_string()).collect::<Vec<_>>().join(""))
+/// ```
+pub fn optimize(code: Vec<UnOptCode>, level: usize) -> (OptState, Vec<OptCode>) {
+    let config = Config::new(level);
+    io::print_log(&*format!("optimizing to level {}", level));
+    optimize_with(code, &config)
 }
@@ -28,6 +28,32 @@ pub fn is_hangul_syllable(c: char) -> bool {
     '\u{AC00}' <= c && c <= '\u{D7A3}'
 }
 
+/// Check if the code ends inside a hangul part
+/// Starting character without its ending character after it is ignored by `parse`,
+/// so the rest of the code may come in the next line.
+///
+/// # Example
+///
+/// ```
+/// use hyeong::parse;
+///
+/// assert_eq!(true, parse::is_incomplete("형. 혀어어"));
+/// assert_eq!(true, parse::is_incomplete("하아앙. 흐으"));
+/// assert_eq!(false, parse::is_incomplete("형. 혀어엉."));
+/// assert_eq!(false, parse::is_incomplete("하앗 흐윽"));
+/// ```
+pub fn is_incomplete(code: &str) -> bool {
+    let mut pending = [false; 3];
+    for c in code.chars() {
+        if let Some(t) = "혀하흐".find(c) {
+            pending[t / 3] = true;
+        } else if let Some(t) = "엉앙앗읏읍윽".find(c) {
+            pending[[0, 1, 1, 2, 2, 2][t / 3]] = false;
+        }
+    }
+    pending.contains(&true)
+}
+
 /// Parse the code to unoptimized code
 /// Since the language itself has no compile error, it never returns error.
 ///
@@ -0,0 +1,307 @@
+use crate::area::Area;
+use crate::code::{Code, OptCode};
+use crate::jump;
+use crate::liveness::Liveness;
+use crate::number::Num;
+use std::collections::HashSet;
+
+/// Longest sequence of commands to fold
+const MAX_WINDOW: usize = 16;
+
+/// Value in symbolic evaluation
+///
+/// - `Num(n)`: known value
+/// - `Base(i)`: `i`-th value from the top of current stack before the sequence
+/// - `Neg(i)`: negated `Base(i)`
+/// - `Other`: any other value
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Num(Num),
+    Base(usize),
+    Neg(usize),
+    Other,
+}
+
+impl Value {
+    fn neg(self) -> Value {
+        match self {
+            Value::Num(mut n) => {
+                n.minus();
+                Value::Num(n)
+            }
+            Value::Base(i) => Value::Neg(i),
+            Value::Neg(i) => Value::Base(i),
+            Value::Other => Value::Other,
+        }
+    }
+
+    /// Reciprocal is only known for known value,
+    /// since reciprocal of reciprocal of `0` is `NaN`
+    fn flip(self) -> Value {
+        match self {
+            Value::Num(mut n) => {
+                n.flip();
+                Value::Num(n)
+            }
+            _ => Value::Other,
+        }
+    }
+
+    fn is_nan(&self) -> bool {
+        match self {
+            Value::Num(n) => n.is_nan(),
+            _ => false,
+        }
+    }
+}
+
+/// Symbolic state of a sequence on one current stack
+struct Window {
+    // values pushed on current stack
+    stack: Vec<Value>,
+    // number of values popped from current stack before the sequence
+    used: usize,
+    // values pushed to other stacks with its location
+    pushes: Vec<(usize, usize, Value)>,
+}
+
+impl Window {
+    fn pop(&mut self) -> Value {
+        match self.stack.pop() {
+            Some(v) => v,
+            None => {
+                self.used += 1;
+                Value::Base(self.used - 1)
+            }
+        }
+    }
+
+    /// Push `v` to stack `idx`, and returns false if it is unknown to be pushed
+    /// `NaN` is not pushed to empty stack, so known `NaN` is not allowed.
+    fn push(&mut self, cur: usize, loc: usize, idx: usize, v: Value) -> bool {
+        if v.is_nan() {
+            return false;
+        }
+        if idx == cur {
+            self.stack.push(v);
+        } else {
+            self.pushes.push((loc, idx, v));
+        }
+        true
+    }
+
+    /// Run command `c` at `loc`, and returns false if it can not be evaluated
+    fn run(&mut self, cur: usize, loc: usize, c: &OptCode) -> bool {
+        let count = c.get_hangul_count();
+        match c.get_type() {
+            0 => {
+                let n = &Num::from_num(count as isize) * &Num::from_num(c.get_dot_count() as isize);
+                self.push(cur, loc, cur, Value::Num(n))
+            }
+            1 | 2 => {
+                let mut n = Some(if c.get_type() == 1 {
+                    Num::zero()
+                } else {
+                    Num::one()
+                });
+                for _ in 0..count {
+                    n = match (n, self.pop()) {
+                        (Some(a), Value::Num(b)) if c.get_type() == 1 => Some(&a + &b),
+                        (Some(a), Value::Num(b)) => Some(&a * &b),
+                        _ => None,
+                    };
+                }
+                self.push(
+                    cur,
+                    loc,
+                    c.get_dot_count(),
+                    n.map_or(Value::Other, Value::Num),
+                )
+            }
+            3 | 4 => {
+                let v = (0..count).map(|_| self.pop()).collect::<Vec<_>>();
+                let mut n = Some(if c.get_type() == 3 {
+                    Num::zero()
+                } else {
+                    Num::one()
+                });
+                for x in v {
+                    let x = if c.get_type() == 3 { x.neg() } else { x.flip() };
+                    n = match (n, &x) {
+                        (Some(a), Value::Num(b)) if c.get_type() == 3 => Some(&a + b),
+                        (Some(a), Value::Num(b)) => Some(&a * b),
+                        _ => None,
+                    };
+                    if !self.push(cur, loc, cur, x) {
+                        return false;
+                    }
+                }
+                self.push(
+                    cur,
+                    loc,
+                    c.get_dot_count(),
+                    n.map_or(Value::Other, Value::Num),
+                )
+            }
+            _ => false,
+        }
+    }
+
+    /// Make code that does the same with the sequence
+    /// `discard` is a stack index that is never read, and `dead(loc, idx)` is whether stack `idx`
+    /// is never read after command at `loc`.
+    fn emit<F>(&self, discard: usize, dead: F) -> Option<Vec<OptCode>>
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let mut res = Vec::new();
+        for (loc, idx, v) in &self.pushes {
+            if *idx > 3 && dead(*loc, *idx) {
+                continue;
+            }
+            match v {
+                Value::Num(n) => {
+                    res.extend(push_num(n, discard)?);
+                    res.push(OptCode::new(1, 1, *idx, 0, Area::Nil));
+                }
+                _ => return None,
+            }
+        }
+
+        let kept = self
+            .stack
+            .iter()
+            .zip((0..self.used).rev())
+            .take_while(|(v, i)| **v == Value::Base(*i))
+            .count();
+        if self.used > kept {
+            res.push(OptCode::new(1, self.used - kept, discard, 0, Area::Nil));
+        }
+        for v in &self.stack[kept..] {
+            match v {
+                Value::Num(n) => res.extend(push_num(n, discard)?),
+                _ => return None,
+            }
+        }
+        Some(res)
+    }
+}
+
+/// Code that pushes integer `n` to current stack
+fn push_num(n: &Num, discard: usize) -> Option<Vec<OptCode>> {
+    let s = n.to_string();
+    if let Ok(t) = s.parse::<usize>() {
+        Some(vec![if t == 0 {
+            OptCode::new(0, 1, 0, 0, Area::Nil)
+        } else {
+            OptCode::new(0, t, 1, 0, Area::Nil)
+        }])
+    } else {
+        let t = s.strip_prefix('-')?.parse::<usize>().ok()?;
+        Some(vec![
+            OptCode::new(0, t, 1, 0, Area::Nil),
+            OptCode::new(3, 1, discard, 0, Area::Nil),
+        ])
+    }
+}
+
+/// Fold sequences of commands with peephole rewrites
+///
+/// `code[..start]` is the code that has already been executed, and it is not changed.
+/// `live` is made by [liveness::analyze](../liveness/fn.analyze.html) of `code`,
+/// and `discard` is a stack index that is never read.
+///
+/// A sequence is folded if
+///
+/// 1. it has no `흑`, and only the last command has area,
+/// 2. commands except the first can not be reached by a heart jump from elsewhere,
+/// 3. current stack is one stack that is not input or output,
+/// 4. and the values it pushes are known, or pushed back as they were, or never read.
+///
+/// So `형` pushes followed by `항` that adds them becomes one `형`,
+/// and `흣` applied twice is removed if the sums are never read.
+/// `흡` applied twice is removed only for known values, since reciprocal of `0` is `NaN`.
+///
+/// # Examples
+///
+/// ```
+/// use hyeong::{liveness, optimize, parse, peephole};
+/// use hyeong::code::Code;
+///
+/// let (_, code) = optimize::optimize(parse::parse("형.. 형... 하앙... 흣.... 흣....".to_string()), 1);
+/// let live = liveness::analyze(&code, 0, 3, &[], None);
+/// let res = peephole::fold(&code, 0, &live, 100);
+///
+/// assert_eq!(1, res.len());
+/// assert_eq!(0, res[0].get_type());
+/// assert_eq!(5, res[0].get_hangul_count() * res[0].get_dot_count());
+/// ```
+pub fn fold(code: &[OptCode], start: usize, live: &Liveness, discard: usize) -> Vec<OptCode> {
+    // commands that can be reached by heart jump
+    let target = code
+        .iter()
+        .map(|c| {
+            let mut res = HashSet::new();
+            jump::area_results(c.get_area(), &mut res);
+            res.iter().any(|&t| t != 0 && t != 13)
+        })
+        .collect::<Vec<_>>();
+    let dead = |loc: usize, idx: usize| !live.is_live_after(loc, idx);
+
+    let mut res = code[..start].to_vec();
+    let mut i = start;
+    while i < code.len() {
+        let cur = live.current_stacks(i);
+        let mut best = None;
+        if cur.len() == 1 && cur[0] >= 3 {
+            let mut window = Window {
+                stack: Vec::new(),
+                used: 0,
+                pushes: Vec::new(),
+            };
+            for j in i..code.len().min(i + MAX_WINDOW) {
+                if (j > i && target[j]) || !window.run(cur[0], j, &code[j]) {
+                    break;
+                }
+                let has_area = !matches!(code[j].get_area(), Area::Nil);
+                if j > i {
+                    if let Some(mut t) = window.emit(discard, dead) {
+                        if t.len() < j - i + 1 && !(has_area && t.is_empty()) {
+                            if let Some(last) = t.last_mut() {
+                                *last = OptCode::new(
+                                    last.get_type(),
+                                    last.get_hangul_count(),
+                                    last.get_dot_count(),
+                                    code[j].get_area_count(),
+                                    code[j].get_area().clone(),
+                                );
+                            }
+                            best = Some((j, t));
+                        }
+                    }
+                }
+                if has_area {
+                    break;
+                }
+            }
+        }
+
+        match best {
+            Some((j, t)) => {
+                for mut c in t {
+                    if let Some(s) = code[i].get_source() {
+                        c.set_source(s);
+                    }
+                    res.push(c);
+                }
+                i = j + 1;
+            }
+            None => {
+                res.push(code[i].clone());
+                i += 1;
+            }
+        }
+    }
+
+    res
+}
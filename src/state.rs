@@ -1,8 +1,39 @@
+use crate::area;
 use crate::code::{Code, OptCode, UnOptCode};
 use crate::number::Num;
+use crate::parse;
+use serde_json::{json, Map, Value};
 use std::collections::HashMap;
 use std::fmt;
 
+/// Version of snapshot made by `UnOptState::to_snapshot`
+pub const SNAPSHOT_VERSION: u64 = 1;
+
+/// String of number that `Num::from_string` can read
+/// Sign of denominator moves to the front.
+fn num_to_string(n: &Num) -> String {
+    let s = n.to_string();
+    if n.is_nan() {
+        return s;
+    }
+    let neg = s.matches('-').count() % 2 == 1;
+    format!("{}{}", if neg { "-" } else { "" }, s.replace('-', ""))
+}
+
+/// Parse number written by `num_to_string`
+fn num_from_string(s: &str) -> Result<Num, String> {
+    if s == Num::nan().to_string()
+        || (!s.is_empty()
+            && s.trim_start_matches('-')
+                .split('/')
+                .all(|x| !x.is_empty() && x.chars().all(|c| c.is_ascii_digit())))
+    {
+        Ok(Num::from_string(s.to_string()))
+    } else {
+        Err(format!("invalid number \"{}\"", s))
+    }
+}
+
 /// State trait
 ///
 /// It defines methods that state structure needs.
@@ -40,10 +71,14 @@ pub trait State {
 
     fn get_all_code(&self) -> Vec<Self::CodeType>;
 
+    fn swap_code(&mut self, code: &mut Vec<Self::CodeType>);
+
     fn set_point(&mut self, id: u128, loc: usize);
 
     fn get_point(&self, id: u128) -> Option<usize>;
 
+    fn remove_point(&mut self, id: u128);
+
     fn get_all_point(&self) -> Vec<(u128, usize)>;
 
     fn set_latest_loc(&mut self, loc: usize);
@@ -159,6 +194,11 @@ impl State for OptState {
         self.code.clone()
     }
 
+    /// Swap code with `code`
+    fn swap_code(&mut self, code: &mut Vec<Self::CodeType>) {
+        std::mem::swap(&mut self.code, code);
+    }
+
     /// Set point for area
     fn set_point(&mut self, id: u128, loc: usize) {
         self.point.insert(id, loc);
@@ -169,6 +209,11 @@ impl State for OptState {
         self.point.get(&id).map(|&x| x)
     }
 
+    /// Remove point for area
+    fn remove_point(&mut self, id: u128) {
+        self.point.remove(&id);
+    }
+
     /// Return all points
     fn get_all_point(&self) -> Vec<(u128, usize)> {
         let mut v = Vec::with_capacity(self.point.len());
@@ -226,6 +271,195 @@ impl UnOptState {
             latest: None,
         }
     }
+
+    /// Make snapshot of state as human-readable JSON
+    ///
+    /// It keeps stacks, code, heart points, current stack and latest location
+    /// with `version` of [SNAPSHOT_VERSION](constant.SNAPSHOT_VERSION.html).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::state::{UnOptState, State};
+    /// use hyeong::number::Num;
+    ///
+    /// let mut a = UnOptState::new();
+    /// a.push_stack(4, Num::new(10, 3));
+    /// a.push_stack(4, Num::from_num(-2));
+    /// a.set_point(130, 0);
+    /// let b = UnOptState::from_snapshot(&a.to_snapshot()).unwrap();
+    ///
+    /// assert_eq!(format!("{:?}", a), format!("{:?}", b));
+    /// assert_eq!(Some(0), b.get_point(130));
+    /// ```
+    pub fn to_snapshot(&self) -> String {
+        let mut stacks = self.stack.iter().collect::<Vec<_>>();
+        stacks.sort_by_key(|x| x.0);
+        let mut points = self.point.iter().collect::<Vec<_>>();
+        points.sort();
+
+        let res = json!({
+            "version": SNAPSHOT_VERSION,
+            "current_stack": self.cur,
+            "latest": self.latest,
+            "stacks": stacks
+                .iter()
+                .map(|(i, v)| (i.to_string(), json!(v.iter().map(num_to_string).collect::<Vec<_>>())))
+                .collect::<Map<_, _>>(),
+            "points": points
+                .iter()
+                .map(|(id, loc)| (id.to_string(), json!(loc)))
+                .collect::<Map<_, _>>(),
+            "code": self
+                .code
+                .iter()
+                .map(|c| {
+                    let (line, col) = c.get_location();
+                    json!({ "line": line, "col": col, "code": c.get_raw() })
+                })
+                .collect::<Vec<_>>(),
+        });
+        format!("{}\n", serde_json::to_string_pretty(&res).unwrap())
+    }
+
+    /// Restore state from snapshot made by `to_snapshot`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::state::UnOptState;
+    ///
+    /// assert!(UnOptState::from_snapshot("{\"version\": 1}").is_err());
+    /// assert!(UnOptState::from_snapshot("{\"version\": 100}").is_err());
+    /// ```
+    pub fn from_snapshot(s: &str) -> Result<UnOptState, String> {
+        let v: Value = serde_json::from_str(s).map_err(|e| format!("invalid snapshot: {}", e))?;
+        let err = |x: &str| format!("invalid snapshot: wrong \"{}\"", x);
+        let index = |x: &str| x.parse::<usize>().map_err(|_| err(x));
+
+        match v["version"].as_u64() {
+            Some(SNAPSHOT_VERSION) => {}
+            Some(t) => return Err(format!("unsupported snapshot version {}", t)),
+            None => return Err(err("version")),
+        }
+
+        let mut res = UnOptState::new();
+        res.cur = v["current_stack"]
+            .as_u64()
+            .ok_or_else(|| err("current_stack"))? as usize;
+        res.latest = match &v["latest"] {
+            Value::Null => None,
+            t => Some(t.as_u64().ok_or_else(|| err("latest"))? as usize),
+        };
+
+        for (i, stack) in v["stacks"].as_object().ok_or_else(|| err("stacks"))? {
+            let stack = stack
+                .as_array()
+                .ok_or_else(|| err(i))?
+                .iter()
+                .map(|x| num_from_string(x.as_str().unwrap_or("")))
+                .collect::<Result<Vec<_>, _>>()?;
+            res.stack.insert(index(i)?, stack);
+        }
+
+        for (id, loc) in v["points"].as_object().ok_or_else(|| err("points"))? {
+            let id = id.parse::<u128>().map_err(|_| err(id))?;
+            let loc = loc.as_u64().ok_or_else(|| err("points"))?;
+            res.point.insert(id, loc as usize);
+        }
+
+        for c in v["code"].as_array().ok_or_else(|| err("code"))? {
+            let raw = c["code"].as_str().ok_or_else(|| err("code"))?;
+            let line = c["line"].as_u64().ok_or_else(|| err("line"))?;
+            let col = c["col"].as_u64().ok_or_else(|| err("col"))?;
+            let parsed = parse::parse(raw.to_string());
+            if parsed.len() != 1 {
+                return Err(format!("invalid snapshot: wrong code \"{}\"", raw));
+            }
+            let p = &parsed[0];
+            res.code.push(UnOptCode::new(
+                p.get_type(),
+                p.get_hangul_count(),
+                p.get_dot_count(),
+                (line as usize, col as usize),
+                p.get_area().clone(),
+                raw.to_string(),
+            ));
+        }
+
+        Ok(res)
+    }
+
+    /// Make compact diff from `before` to this state
+    ///
+    /// Each line is a changed current stack, a changed stack with popped(`-`) and pushed(`+`) values,
+    /// or a newly registered heart point with its code index.
+    /// Stacks are compared by their common bottom part.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::state::{UnOptState, State};
+    /// use hyeong::number::Num;
+    ///
+    /// let mut a = UnOptState::new();
+    /// a.push_stack(3, Num::from_num(4));
+    /// let mut b = a.clone();
+    /// b.pop_stack(3);
+    /// b.push_stack(3, Num::new(1, 2));
+    /// b.set_current_stack(4);
+    /// b.set_point(20, 0);
+    ///
+    /// assert_eq!("current stack: 3 -> 4\nstack 3: -[4] +[1/2]\npoint 1💕: 0\n", b.diff(&a));
+    /// assert_eq!("", a.diff(&a));
+    /// ```
+    pub fn diff(&self, before: &UnOptState) -> String {
+        let values = |v: &[Num]| {
+            v.iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut res = String::new();
+        if self.cur != before.cur {
+            res.push_str(&*format!("current stack: {} -> {}\n", before.cur, self.cur));
+        }
+
+        let mut idx = self
+            .stack
+            .keys()
+            .chain(before.stack.keys())
+            .copied()
+            .collect::<Vec<_>>();
+        idx.sort();
+        idx.dedup();
+        for i in idx {
+            let a = before.stack.get(&i).map_or(&[][..], |x| &x[..]);
+            let b = self.stack.get(&i).map_or(&[][..], |x| &x[..]);
+            let same = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+            let mut change = Vec::new();
+            if same < a.len() {
+                change.push(format!("-[{}]", values(&a[same..])));
+            }
+            if same < b.len() {
+                change.push(format!("+[{}]", values(&b[same..])));
+            }
+            if !change.is_empty() {
+                res.push_str(&*format!("stack {}: {}\n", i, change.join(" ")));
+            }
+        }
+
+        let mut points = self
+            .point
+            .iter()
+            .filter(|(id, loc)| before.point.get(id) != Some(loc))
+            .collect::<Vec<_>>();
+        points.sort();
+        for (id, loc) in points {
+            res.push_str(&*format!("point {}: {}\n", area::point_id_string(*id), loc));
+        }
+        res
+    }
 }
 
 impl State for UnOptState {
@@ -276,6 +510,26 @@ impl State for UnOptState {
         self.code.clone()
     }
 
+    /// Swap code with `code`
+    /// Useful for keeping state without copying code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use hyeong::parse;
+    /// use hyeong::state::{UnOptState, State};
+    ///
+    /// let mut a = UnOptState::new();
+    /// let mut c = parse::parse("형. 항.".to_string());
+    ///
+    /// a.swap_code(&mut c);
+    /// assert_eq!(2, a.get_all_code().len());
+    /// assert!(c.is_empty());
+    /// ```
+    fn swap_code(&mut self, code: &mut Vec<Self::CodeType>) {
+        std::mem::swap(&mut self.code, code);
+    }
+
     /// Set point for area
     fn set_point(&mut self, id: u128, loc: usize) {
         self.point.insert(id, loc);
@@ -286,6 +540,11 @@ impl State for UnOptState {
         self.point.get(&id).map(|&x| x)
     }
 
+    /// Remove point for area
+    fn remove_point(&mut self, id: u128) {
+        self.point.remove(&id);
+    }
+
     /// Return all points
     fn get_all_point(&self) -> Vec<(u128, usize)> {
         let mut v = Vec::with_capacity(self.point.len());
@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod dap_test {
+    use hyeong::{dap, io};
+    use serde_json::Value;
+    use std::fs;
+    use std::io::{BufRead, Read, Write};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::thread;
+
+    /// Requests given to the adapter one by one
+    struct Requests {
+        rx: Receiver<Vec<u8>>,
+        buf: Vec<u8>,
+        pos: usize,
+    }
+
+    impl Read for Requests {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.fill_buf()?.read(buf)?;
+            self.consume(n);
+            Ok(n)
+        }
+    }
+
+    impl BufRead for Requests {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            if self.pos >= self.buf.len() {
+                self.buf = self.rx.recv().unwrap_or_default();
+                self.pos = 0;
+            }
+            Ok(&self.buf[self.pos..])
+        }
+
+        fn consume(&mut self, amt: usize) {
+            self.pos += amt;
+        }
+    }
+
+    /// Output of the adapter sent as a message on each flush
+    struct Messages {
+        tx: Sender<Value>,
+        buf: Vec<u8>,
+    }
+
+    impl Write for Messages {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.buf.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let s = io::handle_error(String::from_utf8(std::mem::take(&mut self.buf)));
+            for x in s.split("Content-Length: ").skip(1) {
+                let msg = serde_json::from_str::<Value>(x.splitn(2, "\r\n\r\n").nth(1).unwrap());
+                self.tx.send(msg.unwrap()).unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    /// Receive messages into `res` until `f` returns true
+    /// `terminated` is updated by the events.
+    fn wait(
+        rx: &Receiver<Value>,
+        res: &mut Vec<Value>,
+        terminated: &mut bool,
+        f: impl Fn(&Value) -> bool,
+    ) {
+        loop {
+            let msg = rx.recv().unwrap();
+            let found = f(&msg);
+            match msg["event"].as_str() {
+                Some("terminated") => *terminated = true,
+                Some("stopped") => *terminated = false,
+                _ => (),
+            }
+            res.push(msg);
+            if found {
+                break;
+            }
+        }
+    }
+
+    /// Runs requests of `tests/dap/{name}.in` and compares with `tests/dap/{name}.out`
+    /// Each line of the files is one message.
+    /// Like an editor, next request is sent after the response,
+    /// and after the program stops unless next request is `pause` or `terminate`.
+    fn helper_function(name: &str) {
+        let path = if cfg!(target_os = "windows") {
+            format!("tests\\dap\\{}", name)
+        } else {
+            format!("tests/dap/{}", name)
+        };
+        let requests = io::handle_error(fs::read_to_string(path.clone() + ".in"))
+            .lines()
+            .map(|x| serde_json::from_str::<Value>(x).unwrap())
+            .collect::<Vec<_>>();
+
+        let (req_tx, req_rx) = mpsc::channel();
+        let (msg_tx, msg_rx) = mpsc::channel();
+        let input = Requests {
+            rx: req_rx,
+            buf: Vec::new(),
+            pos: 0,
+        };
+        let output = Messages {
+            tx: msg_tx,
+            buf: Vec::new(),
+        };
+        let server = thread::spawn(move || dap::serve(input, output));
+
+        let mut res = Vec::new();
+        let mut terminated = false;
+        for (i, req) in requests.iter().enumerate() {
+            let body = req.to_string();
+            let msg = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+            req_tx.send(msg.into_bytes()).unwrap();
+            wait(&msg_rx, &mut res, &mut terminated, |x| {
+                x["request_seq"] == req["seq"]
+            });
+
+            // other requests are done before the response
+            let runs = matches!(
+                req["command"].as_str().unwrap(),
+                "configurationDone" | "continue"
+            );
+            let next = requests.get(i + 1).and_then(|x| x["command"].as_str());
+            let interrupted = matches!(next, Some("pause" | "terminate"));
+            if runs && !interrupted && !terminated {
+                wait(&msg_rx, &mut res, &mut terminated, |x| {
+                    x["event"] == "stopped" || x["event"] == "terminated"
+                });
+            }
+        }
+        drop(req_tx);
+        server.join().unwrap();
+        res.extend(msg_rx.iter());
+
+        let expected = io::handle_error(fs::read_to_string(path + ".out"))
+            .lines()
+            .map(|x| serde_json::from_str::<Value>(x).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn dap_test01() {
+        helper_function("step");
+    }
+
+    #[test]
+    fn dap_test02() {
+        helper_function("breakpoint");
+    }
+
+    #[test]
+    fn dap_test03() {
+        helper_function("pause");
+    }
+}
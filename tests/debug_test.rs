@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod debug_test {
+    use hyeong::io;
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Runs `tests/debug/{name}.script` in batch mode and compares with `tests/debug/{name}.out`
+    /// Debugs `tests/debug/{name}.hyeong` if exists, `tests/debug/simple.hyeong` if not.
+    /// When `level` is not 0, it is compared with `tests/debug/{name}.O{level}.out`.
+    fn helper_function(name: &str, level: usize) {
+        let dir = Path::new("tests").join("debug");
+        let file = if dir.join(format!("{}.hyeong", name)).exists() {
+            format!("{}.hyeong", name)
+        } else {
+            "simple.hyeong".to_string()
+        };
+
+        let output = io::handle_error(
+            Command::new(env!("CARGO_BIN_EXE_hyeong"))
+                .current_dir(&dir)
+                .args(&["debug", &file, "--batch", "-O"])
+                .arg(level.to_string())
+                .arg("--script")
+                .arg(format!("{}.script", name))
+                .output(),
+        );
+        let out = if level == 0 {
+            format!("{}.out", name)
+        } else {
+            format!("{}.O{}.out", name, level)
+        };
+        let expected = io::handle_error(fs::read_to_string(dir.join(out)));
+
+        assert_eq!(
+            expected.lines().collect::<Vec<_>>(),
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn debug_test01() {
+        helper_function("break", 0);
+    }
+
+    #[test]
+    fn debug_test02() {
+        helper_function("edit", 0);
+    }
+
+    #[test]
+    fn debug_test03() {
+        helper_function("input", 0);
+    }
+
+    #[test]
+    fn debug_test04() {
+        helper_function("opt", 0);
+    }
+
+    #[test]
+    fn debug_test05() {
+        helper_function("opt", 1);
+    }
+
+    #[test]
+    fn debug_test06() {
+        helper_function("opt", 2);
+    }
+
+    #[test]
+    fn debug_test07() {
+        helper_function("loop", 0);
+    }
+
+    #[test]
+    fn debug_test08() {
+        helper_function("jump", 0);
+    }
+
+    #[test]
+    fn debug_test09() {
+        helper_function("condition", 0);
+    }
+
+    #[test]
+    fn debug_test10() {
+        helper_function("history", 0);
+    }
+}
@@ -0,0 +1,399 @@
+#[cfg(test)]
+mod differential_test {
+    use hyeong::io::{CustomWriter, QueueReader};
+    use hyeong::optimize::{self, Config};
+    use hyeong::state::{State, UnOptState};
+    use hyeong::{build, execute, parse};
+    use std::io::Write;
+    use std::path::Path;
+    use std::process::{self, Command, Stdio};
+    use std::time::{Duration, Instant};
+    use std::{env, fs, thread};
+
+    /// Steps to run before giving up the program
+    const STEP_LIMIT: usize = 3000;
+
+    /// Time to wait for a compiled program before killing it
+    const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+    const AREAS: &[&str] = &[
+        "",
+        "",
+        "",
+        "",
+        "♥",
+        "💕",
+        "♡",
+        "?💕",
+        "!💖",
+        "?💕!💖",
+        "?♥!♡",
+        "💖?♥",
+    ];
+
+    /// Xorshift random number generator, to make the same programs every time
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// Command of random program: type, hangul count, dot count and area
+    #[derive(Clone, Debug)]
+    struct Cmd(usize, usize, usize, &'static str);
+
+    impl Cmd {
+        fn random(rng: &mut Rng) -> Cmd {
+            let type_ = rng.below(6);
+            let hangul = if rng.below(4) == 0 {
+                2 + rng.below(3)
+            } else {
+                1
+            };
+            let dot = if type_ == 0 {
+                rng.below(6)
+            } else {
+                [0, 1, 2, 3, 3, 3, 4, 4, 5, 5, 6, 7][rng.below(12)]
+            };
+            Cmd(type_, hangul, dot, AREAS[rng.below(AREAS.len())])
+        }
+
+        fn to_code(&self) -> String {
+            let (first, mid, last) = [
+                ("혀", "어", "엉"),
+                ("하", "아", "앙"),
+                ("하", "아", "앗"),
+                ("흐", "으", "읏"),
+                ("흐", "으", "읍"),
+                ("흐", "으", "윽"),
+            ][self.0];
+            let word = if self.1 == 1 {
+                ["형", "항", "핫", "흣", "흡", "흑"][self.0].to_string()
+            } else {
+                format!("{}{}{}", first, mid.repeat(self.1 - 2), last)
+            };
+            format!("{}{}{}", word, ".".repeat(self.2), self.3)
+        }
+    }
+
+    fn to_code(program: &[Cmd]) -> String {
+        program
+            .iter()
+            .map(|c| c.to_code())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn random_input(rng: &mut Rng) -> String {
+        let words = ["0", "12", "-3", "5 7", "hi", "형", "1.5", ""];
+        (0..rng.below(4))
+            .map(|_| words[rng.below(words.len())])
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Stdout, stderr and exit code of a run
+    type Output = (String, String, i32);
+
+    /// Run code on `state` like the `run` command, and `None` if it runs too long
+    fn run_state<T>(mut state: T, code: &[T::CodeType], input: &str) -> Option<Output>
+    where
+        T: State,
+    {
+        let mut ipt = QueueReader::new();
+        ipt.push(input);
+        let mut out = CustomWriter::new(|_| Result::Ok(()));
+        let mut err = CustomWriter::new(|_| Result::Ok(()));
+        let mut steps = 0;
+        let mut exit = 0;
+
+        'outer: for c in code {
+            let mut cur_loc = state.push_code(c.clone());
+            let length = cur_loc + 1;
+            while cur_loc < length {
+                steps += 1;
+                if steps > STEP_LIMIT {
+                    return None;
+                }
+                let (new_state, res) =
+                    execute::execute_one_no_exit(&mut ipt, &mut out, &mut err, state, cur_loc);
+                state = new_state;
+                match res {
+                    Ok(t) => cur_loc = t,
+                    Err(t) => {
+                        exit = t;
+                        break 'outer;
+                    }
+                }
+            }
+        }
+
+        Some((out.to_string(), err.to_string(), exit))
+    }
+
+    /// Run program with optimize `level`
+    fn run(program: &str, input: &str, level: usize) -> Option<Output> {
+        let code = parse::parse(program.to_string());
+        if level == 0 {
+            return run_state(UnOptState::new(), &code, input);
+        }
+
+        let (mut state, opt_code) = optimize::optimize_with(code, &Config::new(level));
+        let mut left = Vec::new();
+        for i in 1..=2 {
+            left.push(
+                state
+                    .get_stack(i)
+                    .drain(..)
+                    .map(|x| optimize::output_char(&x))
+                    .collect::<String>(),
+            );
+        }
+        let (out, err, exit) = run_state(state, &opt_code, input)?;
+        Some((left[0].clone() + &out, left[1].clone() + &err, exit))
+    }
+
+    /// Outputs of each level if they are not the same
+    fn diff(program: &[Cmd], input: &str, levels: &[usize]) -> Option<Vec<Output>> {
+        let code = to_code(program);
+        let res = levels
+            .iter()
+            .map(|&l| run(&code, input, l))
+            .collect::<Option<Vec<_>>>()?;
+        if res.iter().all(|x| x == &res[0]) {
+            None
+        } else {
+            Some(res)
+        }
+    }
+
+    /// Make program that still differs as small as possible
+    fn shrink(mut program: Vec<Cmd>, input: &str, levels: &[usize]) -> Vec<Cmd> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            let mut size = program.len() / 2;
+            while size >= 1 {
+                let mut i = 0;
+                while i + size <= program.len() {
+                    let mut t = program.clone();
+                    t.drain(i..i + size);
+                    if diff(&t, input, levels).is_some() {
+                        program = t;
+                        changed = true;
+                    } else {
+                        i += 1;
+                    }
+                }
+                size /= 2;
+            }
+
+            for i in 0..program.len() {
+                let Cmd(type_, hangul, dot, area) = program[i].clone();
+                let smaller = [
+                    Cmd(type_, hangul, dot, ""),
+                    Cmd(type_, 1, dot, area),
+                    Cmd(type_, hangul, dot / 2, area),
+                    Cmd(type_, hangul, dot.saturating_sub(1), area),
+                ];
+                for c in smaller.iter() {
+                    if c.to_code() == program[i].to_code() {
+                        continue;
+                    }
+                    let mut t = program.clone();
+                    t[i] = c.clone();
+                    if diff(&t, input, levels).is_some() {
+                        program = t;
+                        changed = true;
+                        break;
+                    }
+                }
+            }
+        }
+        program
+    }
+
+    fn helper_function(seed: u64, count: usize, levels: &[usize]) {
+        let mut rng = Rng(seed);
+        for _ in 0..count {
+            let len = 1 + rng.below(14);
+            let program = (0..len).map(|_| Cmd::random(&mut rng)).collect::<Vec<_>>();
+            let input = random_input(&mut rng);
+
+            if diff(&program, &input, levels).is_some() {
+                let program = shrink(program, &input, levels);
+                let res = diff(&program, &input, levels).unwrap();
+                panic!(
+                    "outputs differ\ncode: {}\ninput: {:?}\n{}",
+                    to_code(&program),
+                    input,
+                    levels
+                        .iter()
+                        .zip(res)
+                        .map(|(l, r)| format!("-O{}: {:?}", l, r))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                );
+            }
+        }
+    }
+
+    /// Rust code of program made by the `build` command with optimize `level`
+    fn build_source(program: &str, level: usize) -> String {
+        let code = parse::parse(program.to_string());
+        if level == 0 {
+            build::build_source(UnOptState::new(), &code, 0)
+        } else {
+            let (state, opt_code) = optimize::optimize_with(code, &Config::new(level));
+            build::build_source(state, &opt_code, level)
+        }
+    }
+
+    /// Run compiled program, and kill it if it runs too long
+    fn run_binary(file: &Path, input: &str) -> Output {
+        let mut child = Command::new(file)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // program can exit before reading every input
+        let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+
+        let start = Instant::now();
+        while child.try_wait().unwrap().is_none() {
+            if start.elapsed() > TIME_LIMIT {
+                child.kill().unwrap();
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let output = child.wait_with_output().unwrap();
+        (
+            String::from_utf8_lossy(&output.stdout).to_string(),
+            String::from_utf8_lossy(&output.stderr).to_string(),
+            output.status.code().unwrap_or(-1),
+        )
+    }
+
+    /// Compile `programs` with the `build` backend at each of `levels` and compare with `-O0`
+    ///
+    /// Generated code is put in one crate like the one `build` makes, as a binary for each level.
+    fn helper_function_build(programs: &[(String, String)], levels: &[usize]) {
+        let dir = env::temp_dir().join(format!("hyeong_differential_{}", process::id()));
+        let src = dir.join("src");
+        fs::create_dir_all(src.join("bin")).unwrap();
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"hyeong-build\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+        )
+        .unwrap();
+        for file in &["number.rs", "big_number.rs"] {
+            fs::copy(
+                Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join(file),
+                src.join(file),
+            )
+            .unwrap();
+        }
+        fs::write(src.join("lib.rs"), "pub mod big_number;\npub mod number;\n").unwrap();
+        for (i, (program, _)) in programs.iter().enumerate() {
+            for &level in levels {
+                fs::write(
+                    src.join("bin").join(format!("p{}_{}.rs", i, level)),
+                    build_source(program, level),
+                )
+                .unwrap();
+            }
+        }
+
+        let status = Command::new(env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args(&["build", "--quiet", "--offline", "--manifest-path"])
+            .arg(dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(dir.join("target"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "generated code does not compile");
+
+        for (i, (program, input)) in programs.iter().enumerate() {
+            let expected = run(program, input, 0).unwrap();
+            for &level in levels {
+                let file = dir.join("target").join("debug").join(format!(
+                    "p{}_{}{}",
+                    i,
+                    level,
+                    env::consts::EXE_SUFFIX
+                ));
+                let res = run_binary(&file, input);
+                assert_eq!(
+                    expected, res,
+                    "outputs differ\ncode: {}\ninput: {:?}\nbuild -O{}",
+                    program, input, level
+                );
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn differential_test01() {
+        helper_function(1, 300, &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn differential_test02() {
+        helper_function(2, 300, &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn differential_test03() {
+        helper_function(3, 300, &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn differential_test04() {
+        let mut programs = vec![
+            (String::from("흑.?💕"), String::new()),
+            (String::from("흑 항."), String::from("7\n")),
+            (
+                String::from("형 흣........💕 흣.... 형. 하앙... 흣. 흑... 흐읏....!💕"),
+                String::new(),
+            ),
+            (
+                String::from("형. 형.. 형. 흑...💘 항.... 하앙... 항...♡ 흑...💘 ! 흣...흑."),
+                String::new(),
+            ),
+            (
+                fs::read_to_string("examples/hello_world/hello_world.hyeong").unwrap(),
+                String::new(),
+            ),
+            (
+                fs::read_to_string("examples/a_plus_b/a_plus_b.hyeong").unwrap(),
+                String::from("12 34\n"),
+            ),
+        ];
+
+        // random programs that finish in time
+        let mut rng = Rng(4);
+        while programs.len() < 24 {
+            let len = 1 + rng.below(14);
+            let program = (0..len).map(|_| Cmd::random(&mut rng)).collect::<Vec<_>>();
+            let input = random_input(&mut rng);
+            if run(&to_code(&program), &input, 0).is_some() {
+                programs.push((to_code(&program), input));
+            }
+        }
+
+        helper_function_build(&programs, &[0, 1, 2, 3]);
+    }
+}
@@ -62,4 +62,26 @@ mod execute_test {
             "",
         );
     }
+
+    #[test]
+    fn execute_test08() {
+        let mut ipt = io::CustomReader::new(String::new());
+        let mut out = io::CustomWriter::new(|_| Result::Ok(()));
+        let mut err = io::CustomWriter::new(|_| Result::Ok(()));
+        let mut state = UnOptState::new();
+        let mut exit = None;
+
+        for c in parse::parse("흑.?💕 형.".to_string()) {
+            let (t, e) = execute::execute_no_exit(&mut ipt, &mut out, &mut err, state, &c);
+            state = t;
+            exit = e;
+            if exit.is_some() {
+                break;
+            }
+        }
+
+        assert_eq!(Some(0), exit);
+        assert_eq!("너무 커엇...", out.to_string());
+        assert_eq!("", err.to_string());
+    }
 }
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod interpreter_test {
+    use hyeong::io;
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    /// Runs `hyeong repl --raw` with `input` and compares stdout with `expected`
+    fn helper_function(input: &str, expected: &str) {
+        let mut child = io::handle_error(
+            Command::new(env!("CARGO_BIN_EXE_hyeong"))
+                .args(&["repl", "--raw"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn(),
+        );
+        io::handle_error(child.stdin.take().unwrap().write_all(input.as_bytes()));
+        let output = io::handle_error(child.wait_with_output());
+
+        assert_eq!(expected, String::from_utf8_lossy(&output.stdout));
+    }
+
+    #[test]
+    fn interpreter_test01() {
+        helper_function(
+            ":load examples/hello_world/hello_world.hyeong\n",
+            "Hello, world!\n",
+        );
+    }
+
+    #[test]
+    fn interpreter_test02() {
+        helper_function("혀어\n어엉.\n:stack 3\n", "stack 3: [4]\n");
+    }
+
+    #[test]
+    fn interpreter_test03() {
+        helper_function(
+            ":input A\n흑 항. 항...\n:stack 3\n:undo\n:state\n흑 항. 항...\n:state\n",
+            "Astack 3: [10]\ncurrent stack: 3\nAcurrent stack: 0\nstack 0: []\nstack 3: [10]\n",
+        );
+    }
+
+    #[test]
+    fn interpreter_test04() {
+        helper_function(
+            "형... 흑. 항.\n형..\n:state\n:parse 형.. 흑\n:foo\n",
+            "\u{3}\u{2}current stack: 1\nstack 3: [3]\n1:0 형_1_2 : _\n1:4 흑_1_0 : _\n",
+        );
+    }
+
+    #[test]
+    fn interpreter_test05() {
+        helper_function(
+            ":diff\n형형형 흣.💕\n흑..\n:diff\n흑...\n",
+            "\u{0}stack 3: +[0, 0, 0]\npoint 1💕: 3\ncurrent stack: 3 -> 2\n",
+        );
+    }
+
+    #[test]
+    fn interpreter_test06() {
+        helper_function("혀어\n\n:stack 3\n", "stack 3: []\n");
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod jump_test {
+    use hyeong::jump::{self, Jump};
+    use hyeong::{optimize, parse};
+
+    fn helper_function(code: &str, res: Vec<(u128, Jump)>) {
+        let parsed = parse::parse(code.to_string());
+        assert_eq!(res, jump::analyze(&parsed, 0, &[]).get_all());
+    }
+
+    #[test]
+    fn jump_test01() {
+        helper_function("형. 흣.", vec![]);
+    }
+
+    #[test]
+    fn jump_test02() {
+        helper_function("형.💕 형.💕 형.💕", vec![(20, Jump::Static(0))]);
+    }
+
+    #[test]
+    fn jump_test03() {
+        helper_function("형.?💕 형.💕", vec![(20, Jump::Dynamic)]);
+    }
+
+    #[test]
+    fn jump_test04() {
+        helper_function("형.💕?💕 형.?💕", vec![(20, Jump::Static(0))]);
+    }
+
+    #[test]
+    fn jump_test05() {
+        helper_function(
+            "형 흣........💕 흣.... 형. 하앙... 흣. 흑... 흐읏....!💕",
+            vec![(132, Jump::Static(1))],
+        );
+    }
+
+    #[test]
+    fn jump_test06() {
+        helper_function(
+            "형.♡ 형..💕 형.💕",
+            vec![(20, Jump::Static(2)), (36, Jump::Static(1))],
+        );
+    }
+
+    #[test]
+    fn jump_test07() {
+        let parsed = parse::parse("형.?💕 형. 형.💕".to_string());
+        let table = jump::analyze(&parsed, 2, &[(20, 0)]);
+        assert_eq!(vec![(20, Jump::Static(0))], table.get_all());
+
+        let table = jump::analyze(&parsed, 2, &[]);
+        assert_eq!(vec![(20, Jump::Dynamic)], table.get_all());
+    }
+
+    #[test]
+    fn jump_test08() {
+        let parsed = parse::parse("형. 형.💕 형. 형.💕".to_string());
+        let (_, opt_code) = optimize::optimize(parsed, 1);
+        let table = jump::analyze(&opt_code, 0, &[]).map_location(|x| x * 2);
+        assert_eq!(vec![(20, Jump::Static(2))], table.get_all());
+    }
+}
@@ -0,0 +1,49 @@
+#[cfg(test)]
+mod liveness_test {
+    use hyeong::{liveness, parse};
+    use std::collections::HashMap;
+
+    fn helper_function(code: &str, size: usize, dots: Vec<usize>) {
+        let parsed = parse::parse(code.to_string());
+        let res = liveness::allocate(&parsed, 0, 3, &[], None, &HashMap::new());
+        assert_eq!(size, res.size);
+        assert_eq!(dots, res.dots);
+    }
+
+    #[test]
+    fn liveness_test01() {
+        helper_function("형. 흣.", 4, vec![1, 1]);
+    }
+
+    #[test]
+    fn liveness_test02() {
+        helper_function("형... 항.... 항..... 항.", 4, vec![3, 4, 4, 1]);
+    }
+
+    #[test]
+    fn liveness_test03() {
+        helper_function(
+            "형... 흑.... 항..... 흑..... 항....",
+            6,
+            vec![3, 4, 5, 5, 6],
+        );
+    }
+
+    #[test]
+    fn liveness_test04() {
+        helper_function(
+            "형. 흑.... 항. 흑..... 형.. 항. 흑...... 형... 항.",
+            5,
+            vec![1, 4, 1, 4, 2, 1, 4, 3, 1],
+        );
+    }
+
+    #[test]
+    fn liveness_test05() {
+        helper_function(
+            "형 흣........💕 흑.... 형. 하앙... 흑... 흐읏....!💕 항.",
+            5,
+            vec![0, 5, 4, 1, 3, 3, 4, 1],
+        );
+    }
+}
@@ -50,4 +50,51 @@ mod number_test {
 
         assert_eq!(Num::new(-3, 10), a);
     }
+
+    #[test]
+    fn compare_test04() {
+        let a = Num::new(-10, 3);
+        let b = Num::new(-7, 4);
+        let mut c = Num::from_num(-3);
+        c.flip();
+
+        assert_eq!(Some(Ordering::Less), a.partial_cmp(&b));
+        assert_eq!(Some(Ordering::Greater), b.partial_cmp(&a));
+        assert_eq!(Some(Ordering::Less), c.partial_cmp(&Num::zero()));
+        assert_eq!(Some(Ordering::Greater), Num::new(10, 3).partial_cmp(&c));
+        assert_eq!(Some(Ordering::Equal), a.partial_cmp(&Num::new(-10, 3)));
+        assert_eq!(None, a.partial_cmp(&Num::nan()));
+    }
+
+    #[test]
+    fn compare_test05() {
+        let three = Num::from_num(3);
+
+        assert_eq!(Some(Ordering::Less), Num::from_num(2).partial_cmp(&three));
+        assert_eq!(Some(Ordering::Equal), Num::from_num(3).partial_cmp(&three));
+        assert_eq!(
+            Some(Ordering::Greater),
+            Num::from_num(5).partial_cmp(&three)
+        );
+        assert_eq!(
+            Some(Ordering::Less),
+            Num::new(1, 2).partial_cmp(&Num::one())
+        );
+        assert_eq!(
+            Some(Ordering::Greater),
+            Num::new(1, 2).partial_cmp(&Num::new(1, 3))
+        );
+        assert_eq!(
+            Some(Ordering::Less),
+            Num::new(-1, 2).partial_cmp(&Num::new(-1, 3))
+        );
+        assert_eq!(
+            Some(Ordering::Equal),
+            Num::new(2, 4).partial_cmp(&Num::new(1, 2))
+        );
+        assert_eq!(
+            Some(Ordering::Equal),
+            Num::zero().partial_cmp(&Num::new(0, 5))
+        );
+    }
 }
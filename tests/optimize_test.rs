@@ -1,27 +1,41 @@
 ﻿#[cfg(test)]
 mod optimize_test {
+    use hyeong::number::Num;
     use hyeong::state::State;
     use hyeong::{execute, io, optimize, parse};
     use std::io::Write;
+    use std::process::{self, Command, Stdio};
+    use std::time::{Duration, Instant};
+    use std::{env, fs, thread};
 
     fn helper_function(code: &str, stdin: &str, stdout: &str, stderr: &str, level: usize) {
+        helper_function_passes(code, stdin, stdout, stderr, &optimize::Config::new(level));
+    }
+
+    fn helper_function_passes(
+        code: &str,
+        stdin: &str,
+        stdout: &str,
+        stderr: &str,
+        config: &optimize::Config,
+    ) {
         let un_opt_code = parse::parse(code.to_string());
         let mut ipt = io::CustomReader::new(stdin.to_string());
         let mut out = io::CustomWriter::new(|_| Result::Ok(()));
         let mut err = io::CustomWriter::new(|_| Result::Ok(()));
         let mut out_str = String::from("");
         let mut err_str = String::from("");
-        let (mut opt_state, opt_code) = optimize::optimize(un_opt_code, level);
+        let (mut opt_state, opt_code) = optimize::optimize_with(un_opt_code, config);
         if !opt_state.get_stack(1).is_empty() {
             for num in opt_state.get_stack(1).iter() {
-                out_str.push_str(&*format!("{}", num.floor().to_int() as u8 as char));
+                out_str.push_str(&*format!("{}", optimize::output_char(num)));
             }
             io::handle_error(out.flush());
             opt_state.get_stack(1).clear();
         }
         if !opt_state.get_stack(2).is_empty() {
             for num in opt_state.get_stack(2).iter() {
-                err_str.push_str(&*format!("{}", num.floor().to_int() as u8 as char));
+                err_str.push_str(&*format!("{}", optimize::output_char(num)));
             }
             io::handle_error(err.flush());
             opt_state.get_stack(2).clear();
@@ -118,4 +132,198 @@ mod optimize_test {
     fn optimize_test13() {
         helper_function("형. 흣... 흑 흑.", "", "1", "", 2);
     }
+
+    #[test]
+    fn optimize_test14() {
+        let code = "흑 항. 형 흑... 흣........💕 흣.... 형. 하앙... 흣. 흑... 흐읏....!💕";
+        for passes in &[
+            "passes=",
+            "passes=renumber",
+            "passes=prefix-eval",
+            "passes=prefix-eval,renumber",
+            "passes=renumber,prefix-eval,renumber",
+        ] {
+            let mut config = optimize::Config::new(0);
+            config.set(passes).unwrap();
+            helper_function_passes(code, "7", "712345678", "", &config);
+        }
+    }
+
+    #[test]
+    fn optimize_test15() {
+        helper_function("혀어어어어어어어엉........ 핫. 혀엉..... 흑... 하앗... 흐윽... 형.  하앙.혀엉.... 하앙... 흐윽... 항. 항. 형... 하앙. 흐으윽... 형... 흡... 혀엉..하아아앗. 혀엉.. 흡... 흐읍... 형.. 하앗. 하아앙... 형... 하앙... 흐윽...혀어어엉.. 하앙. 항. 형... 하앙. 혀엉.... 하앙. 흑... 항. 형... 흡  하앗.", "", "Hello, world!", "", 3);
+    }
+
+    #[test]
+    fn optimize_test16() {
+        helper_function(
+            "형 흣........💕 흣.... 형. 하앙... 흣. 흑... 흐읏....!💕",
+            "",
+            "12345678",
+            "",
+            3,
+        );
+    }
+
+    #[test]
+    fn optimize_test17() {
+        helper_function("형 형 흣
+        흑💘!💘 흑...! 하앙... 혀엉... .. 하앗... 흑!?! 흑... 혀어어 어어어 어엉... ... 흣... . 하앙... 흑 혀엉... .. 흣... . 하앙 흑...! 흑?💘?
+        흑...! 항... . 혀엉... .. 흡... . 하앗...
+        흑!?! 흑...!
+        형 형
+        흑💕!💕 흑...! 하앙... 혀엉... .. 하앗... 흑!?! 흑... 혀어어 어어어 어엉... ... 흣... . 하앙... 흑 혀엉... .. 흣... . 하앙 흑...! 흑?💕?
+        흑...! 항... . 혀엉... .. 흡... . 하앗...
+        흐읏.", "1111 1234", "2345", "", 3);
+    }
+
+    #[test]
+    fn optimize_test18() {
+        let mut config = optimize::Config::new(0);
+        config.set("passes=renumber,liveness").unwrap();
+        helper_function_passes(
+            "흑 항. 형... 흑.... 항. 흑..... 형.. 항. 흑...... 형... 항. 항.......",
+            "7",
+            "7\u{3}\u{2}\u{3}",
+            "",
+            &config,
+        );
+    }
+
+    #[test]
+    fn optimize_test19() {
+        let mut config = optimize::Config::new(0);
+        config.set("passes=peephole").unwrap();
+        helper_function_passes(
+            "형.. 형... 하앙... 흣.... 흣.... 항.. 형 흑.... 흣..... 흣..... 항.. 형. 형. 하앙... 항..",
+            "",
+            "",
+            "\u{5}\u{0}\u{5}",
+            &config,
+        );
+    }
+
+    #[test]
+    fn optimize_test20() {
+        let mut config = optimize::Config::new(0);
+        config.set("passes=renumber,peephole,liveness").unwrap();
+        helper_function_passes(
+            "흑 항. 형 흑... 흣........💕 흣.... 형. 하앙... 흣. 흑... 흐읏....!💕",
+            "7",
+            "712345678",
+            "",
+            &config,
+        );
+    }
+
+    #[test]
+    fn optimize_test21() {
+        let code = parse::parse("형💕 형💕 형".to_string());
+        let (_, c, r) = optimize::optimize_report(code, &optimize::Config::new(2));
+        assert_eq!(2, c.len());
+        assert_eq!(Some((1, 3, Some(optimize::Stop::Loop))), r.prefix_eval);
+        assert_eq!(
+            "renumber: no stacks\nprefix-eval: ran 1 of 3 commands, stopped by 100 jumps\n\
+             initial current stack: 3\ninitial stack 3: [0]\nheart point 0💕: 0\n",
+            r.to_string()
+        );
+    }
+
+    #[test]
+    fn optimize_test22() {
+        let code = parse::parse("형. 흑.. 항 형 흑...... 형.".to_string());
+        let (_, c, r) = optimize::optimize_report(code, &optimize::Config::new(2));
+        assert_eq!(4, c.len());
+        assert_eq!(Some(vec![(6, 4)]), r.stack_map);
+        assert_eq!(Some((2, 6, Some(optimize::Stop::Exit))), r.prefix_eval);
+        assert_eq!(vec![(2, vec![Num::one()]), (3, vec![Num::one()])], r.stacks);
+        assert_eq!(2, r.current_stack);
+    }
+
+    #[test]
+    fn optimize_test23() {
+        let code = parse::parse("형... 항.".to_string());
+        let (_, c, r) = optimize::optimize_report(code, &optimize::Config::new(1));
+        assert_eq!(2, c.len());
+        assert_eq!(None, r.prefix_eval);
+        assert_eq!(
+            "renumber: no stacks\ninitial current stack: 3\n",
+            r.to_string()
+        );
+    }
+
+    #[test]
+    fn optimize_test24() {
+        helper_function("흑.", "", "너무 커엇...", "", 0);
+        helper_function("흑.", "", "너무 커엇...", "", 2);
+    }
+
+    #[test]
+    fn optimize_test25() {
+        let code = parse::parse("흑.?💕".to_string());
+        let (mut s, c, r) = optimize::optimize_report(code, &optimize::Config::new(2));
+        assert_eq!(1, c.len());
+        assert_eq!(Some((0, 1, Some(optimize::Stop::Exit))), r.prefix_eval);
+        assert!(s.get_stack(1).is_empty());
+    }
+
+    #[test]
+    fn optimize_test26() {
+        let code = parse::parse("형... 흑 항. 형.. 항.".to_string());
+        let (mut s, c, r) = optimize::optimize_report(code, &optimize::Config::new(2));
+        assert_eq!(3, c.len());
+        assert_eq!(Some((2, 5, Some(optimize::Stop::Input))), r.prefix_eval);
+        assert_eq!(0, s.current_stack());
+        assert_eq!(&vec![Num::from_num(3)], s.get_stack(0));
+    }
+
+    #[test]
+    fn optimize_test27() {
+        // `build` keeps its crate in home directory, so home is moved to a directory of this run
+        let dir = env::temp_dir().join(format!("hyeong_optimize_test27_{}", process::id()));
+        io::handle_error(fs::create_dir_all(&dir));
+        let file = dir.join("input.hyeong");
+        io::handle_error(fs::write(&file, "흑 항."));
+        let home = env::var("HOME").unwrap_or_default();
+
+        for level in &["2", "3"] {
+            let mut child = io::handle_error(
+                Command::new(env!("CARGO_BIN_EXE_hyeong"))
+                    .args(&["build", "--emit", "opt-report", "-O", level, "-o"])
+                    .arg(dir.join("output"))
+                    .arg(&file)
+                    .env("HOME", &dir)
+                    .env("USERPROFILE", &dir)
+                    .env(
+                        "CARGO_HOME",
+                        env::var("CARGO_HOME").unwrap_or(format!("{}/.cargo", home)),
+                    )
+                    .env(
+                        "RUSTUP_HOME",
+                        env::var("RUSTUP_HOME").unwrap_or(format!("{}/.rustup", home)),
+                    )
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null())
+                    .spawn(),
+            );
+            // stdin is kept open without any input, so reading it blocks `build`
+            let stdin = child.stdin.take().unwrap();
+            let start = Instant::now();
+            while io::handle_error(child.try_wait()).is_none() {
+                if start.elapsed() > Duration::from_secs(120) {
+                    io::handle_error(child.kill());
+                    panic!("build is waiting for input");
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            drop(stdin);
+
+            let output = io::handle_error(child.wait_with_output());
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            assert!(stdout.contains("prefix-eval: ran 1 of 2 commands, stopped by input read"));
+            assert!(stdout.contains("compiling to rust"));
+        }
+        io::handle_error(fs::remove_dir_all(&dir));
+    }
 }
@@ -197,4 +197,16 @@ mod parse_test {
             format!("{:?}", t[1])
         );
     }
+
+    #[test]
+    fn incomplete_test01() {
+        let code = "혀어어\n엉. 하아\n\n앗.. 흐\n으\n읍";
+        let lines = code.split('\n').collect::<Vec<_>>();
+
+        for i in 1..lines.len() {
+            assert!(parse::is_incomplete(&lines[..i].join("\n")));
+        }
+        assert!(!parse::is_incomplete(code));
+        assert_eq!(3, parse::parse(code.to_string()).len());
+    }
 }
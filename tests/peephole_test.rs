@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod peephole_test {
+    use hyeong::code::Code;
+    use hyeong::{liveness, optimize, parse, peephole};
+
+    fn helper_function(code: &str, res: Vec<(u8, usize, usize)>) {
+        let (_, code) = optimize::optimize(parse::parse(code.to_string()), 1);
+        let live = liveness::analyze(&code, 0, 3, &[], None);
+        let folded = peephole::fold(&code, 0, &live, 100);
+        assert_eq!(
+            res,
+            folded
+                .iter()
+                .map(|c| (c.get_type(), c.get_hangul_count(), c.get_dot_count()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn peephole_test01() {
+        helper_function("형.. 형... 하앙... 흣.... 흣....", vec![(0, 5, 1)]);
+    }
+
+    #[test]
+    fn peephole_test02() {
+        helper_function(
+            "형 흑.... 흣..... 흣..... 항.",
+            vec![(0, 1, 0), (5, 1, 4), (1, 1, 1)],
+        );
+    }
+
+    #[test]
+    fn peephole_test03() {
+        helper_function("형.. 흣.... 항.", vec![(0, 1, 2), (3, 1, 4), (1, 1, 1)]);
+    }
+
+    #[test]
+    fn peephole_test04() {
+        helper_function("형. 형. 하앙... 항.", vec![(0, 2, 1), (1, 1, 1)]);
+    }
+
+    #[test]
+    fn peephole_test05() {
+        helper_function(
+            "형. 형.💕 하앙... 항.",
+            vec![(0, 1, 1), (0, 1, 1), (1, 2, 3), (1, 1, 1)],
+        );
+    }
+
+    #[test]
+    fn peephole_test06() {
+        helper_function("형.. 흡.... 흡.... 항.", vec![(0, 2, 1), (1, 1, 1)]);
+    }
+}